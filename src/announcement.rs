@@ -0,0 +1,236 @@
+use std::{borrow::Cow, path::Path, time::Duration};
+
+use anyhow::{Context, Result};
+use rosu_pp::{Beatmap, BeatmapExt};
+use rosu_v2::prelude::GameMods;
+use serenity::{
+    builder::{CreateEmbed, CreateMessage},
+    model::{application::component::ButtonStyle, channel::AttachmentType},
+    utils::Color,
+};
+use tokio::fs;
+
+use crate::{commands::Settings, play_stats::PlayStats, process_replays::Data, util::OsuFile};
+
+/// How a video was rendered.
+pub struct RenderInfo {
+    pub duration: Duration,
+    /// Name of the danser settings file, either the user's id or `default`
+    pub settings: String,
+}
+
+/// The message that lets the user know that the video is ready.
+pub struct Announcement {
+    content: String,
+    embed: CreateEmbed,
+    /// File name and content of the map's background, shown as thumbnail
+    background: Option<(String, Vec<u8>)>,
+}
+
+impl Announcement {
+    pub async fn new(
+        data: &Data,
+        title: &str,
+        osu_file: &OsuFile,
+        beatmap: &Beatmap,
+        stats: Option<&PlayStats>,
+        render: &RenderInfo,
+    ) -> Self {
+        let metadata = &osu_file.metadata;
+
+        let background = match osu_file.background {
+            Some(ref path) => match read_background(path).await {
+                Ok(background) => Some(background),
+                Err(why) => {
+                    warn!("{:?}", why.context("failed to read map background"));
+
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let skin = match current_skin(&render.settings).await {
+            Ok(skin) => skin,
+            Err(why) => {
+                warn!("{:?}", why.context("failed to get skin of render"));
+
+                "unknown".to_owned()
+            }
+        };
+
+        let mut description = format!(
+            "**{} - {} [{}]**\nMapped by {}",
+            metadata.artist, metadata.title, metadata.version, metadata.creator
+        );
+
+        if let Some(ref score) = data.score {
+            description.push('\n');
+            description.push_str(&score.to_string());
+        }
+
+        let mut embed = CreateEmbed::default();
+
+        embed
+            .title(title)
+            .description(description)
+            .color(Color::new(15785176));
+
+        match stats {
+            Some(stats) => {
+                let mods = GameMods::from_bits(data.replay.mods.bits())
+                    .unwrap_or_default()
+                    .to_string();
+
+                let pp = if stats.is_full_combo() {
+                    format!("{:.2}", stats.pp)
+                } else {
+                    format!("{:.2} ({:.2} for FC)", stats.pp, stats.fc_pp)
+                };
+
+                let player = data.replay.player_name.as_deref().unwrap_or("unknown");
+
+                embed
+                    .field("Player", player, true)
+                    .field("Stars", format!("{:.2}⭐", stats.stars), true)
+                    .field("Mods", mods, true)
+                    .field("Grade", stats.grade, true)
+                    .field("Accuracy", format!("{:.2}%", stats.acc), true)
+                    .field(
+                        "Combo",
+                        format!(
+                            "{}/{}x • {} {}",
+                            stats.combo,
+                            stats.max_combo,
+                            stats.misses,
+                            if stats.misses == 1 { "miss" } else { "misses" }
+                        ),
+                        true,
+                    )
+                    .field("PP", pp, false);
+            }
+            // Players of a knockout may use different mods so only the map's stars are shown
+            None => {
+                let stars = beatmap.stars(0, None).stars();
+
+                embed.field("Players", data.knockout.len() + 1, true).field(
+                    "Stars",
+                    format!("{stars:.2}⭐"),
+                    true,
+                );
+            }
+        }
+
+        let settings = if render.settings == "default" {
+            "default settings"
+        } else {
+            "personal settings"
+        };
+
+        embed.field(
+            "Render",
+            format!(
+                "Took {} with {settings} and the skin `{skin}`",
+                format_duration(render.duration)
+            ),
+            false,
+        );
+
+        Self {
+            content: format!("<@{}> your replay is ready!", data.user),
+            embed,
+            background,
+        }
+    }
+
+    /// Bytes of the background that is attached to the message.
+    pub fn background_size(&self) -> u64 {
+        self.background
+            .as_ref()
+            .map_or(0, |(_, bytes)| bytes.len() as u64)
+    }
+
+    /// The same announcement without the background as thumbnail.
+    pub fn without_background(&self) -> Self {
+        Self {
+            content: self.content.clone(),
+            embed: self.embed.clone(),
+            background: None,
+        }
+    }
+
+    /// Fill in the message, adding a button to the video if there is a `link`.
+    pub fn create<'a, 'b>(
+        &'a self,
+        m: &'b mut CreateMessage<'a>,
+        link: Option<&str>,
+    ) -> &'b mut CreateMessage<'a> {
+        m.content(&self.content);
+
+        let mut embed = self.embed.clone();
+
+        if let Some(link) = link {
+            embed.url(link);
+
+            m.components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|button| {
+                        button
+                            .style(ButtonStyle::Link)
+                            .label("Watch video")
+                            .url(link)
+                    })
+                })
+            });
+        }
+
+        if let Some((ref name, ref bytes)) = self.background {
+            embed.thumbnail(format!("attachment://{name}"));
+
+            m.add_file(AttachmentType::Bytes {
+                data: Cow::Borrowed(bytes),
+                filename: name.to_owned(),
+            });
+        }
+
+        m.set_embed(embed)
+    }
+}
+
+async fn read_background(path: &Path) -> Result<(String, Vec<u8>)> {
+    let bytes = fs::read(path)
+        .await
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+
+    // Discord only shows attachments in embeds if their name has no spaces
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("jpg");
+
+    Ok((format!("background.{ext}"), bytes))
+}
+
+/// The skin that is selected in the danser settings file.
+async fn current_skin(settings: &str) -> Result<String> {
+    let path = format!("../danser/settings/{settings}.json");
+
+    let content = fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("failed to read `{path}`"))?;
+
+    let settings: Settings = serde_json::from_str(&content)
+        .with_context(|| format!("failed to deserialize `{path}`"))?;
+
+    Ok(settings.skin.current_skin)
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+
+    if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{secs}s")
+    }
+}
@@ -5,6 +5,7 @@ use serenity::{
     framework::standard::{macros::check, Args, CommandOptions, Reason},
     model::{
         channel::{Channel, Message},
+        id::UserId,
         Permissions,
     },
 };
@@ -59,11 +60,7 @@ async fn bot_owner_check(
     _: &mut Args,
     _: &CommandOptions,
 ) -> Result<(), Reason> {
-    let owners_as_string = env::var("BOT_OWNER").expect("Expected token BOT_OWNER from the env");
-
-    let owners: Vec<&str> = owners_as_string.split(';').collect();
-
-    if owners.contains(&msg.author.id.to_string().as_str()) {
+    if is_bot_owner(msg.author.id) {
         return Ok(());
     }
 
@@ -71,3 +68,11 @@ async fn bot_owner_check(
         "Lacking required permission to run command".to_string(),
     ))
 }
+
+pub fn is_bot_owner(user: UserId) -> bool {
+    let owners_as_string = env::var("BOT_OWNER").expect("Expected token BOT_OWNER from the env");
+
+    let owners: Vec<&str> = owners_as_string.split(';').collect();
+
+    owners.contains(&user.to_string().as_str())
+}
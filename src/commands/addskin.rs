@@ -52,7 +52,7 @@ impl Display for FileCounter {
 #[description = "**Requires osu! skin attachment**\nAllows you to upload custom skins"]
 async fn addskin(ctx: &serenity::prelude::Context, msg: &Message) -> CommandResult {
     let attachment = match msg.attachments.last() {
-        Some(a) if matches!(a.filename.split('.').last(), Some("osk")) => a,
+        Some(a) if matches!(a.filename.split('.').next_back(), Some("osk")) => a,
         Some(_) | None => {
             msg.reply(&ctx, "The file you have sent is not a skin file!")
                 .await?;
@@ -0,0 +1,145 @@
+use std::fmt::Write;
+
+use rosu_v2::prelude::GameMods;
+use serenity::{
+    framework::standard::{macros::command, CommandResult},
+    model::prelude::Message,
+    prelude::Context,
+    utils::Color,
+};
+
+use crate::process_replays::{load_analyzed_replay, LoadedReplay};
+
+#[command]
+#[description = "**Requires Replay Attachment**\nAnalyze a replay without rendering it.\n\
+Shows the unstable rate, hit errors, accuracy throughout the map, slider breaks, \
+key usage and cursor speed."]
+async fn analyze(ctx: &Context, msg: &Message) -> CommandResult {
+    let (
+        LoadedReplay {
+            replay, metadata, ..
+        },
+        analysis,
+    ) = match load_analyzed_replay(ctx, msg, "analyze").await? {
+        Some(analyzed) => analyzed,
+        None => return Ok(()),
+    };
+
+    let replay = &replay;
+
+    let mods = match GameMods::from_bits(replay.mods.bits()).unwrap_or_default() {
+        GameMods::NoMod => String::new(),
+        mods => format!(" +{mods}"),
+    };
+
+    let description = format!(
+        "**{} - {} [{}]**{mods}\nPlayed by {}",
+        metadata.artist,
+        metadata.title,
+        metadata.version,
+        replay.player_name.as_deref().unwrap_or("unknown"),
+    );
+
+    let hit_error = format!(
+        "UR **{:.2}** • mean {:.2}ms {}\n{} early • {} late hits",
+        analysis.unstable_rate,
+        analysis.mean_error.abs(),
+        if analysis.mean_error < 0.0 {
+            "early"
+        } else {
+            "late"
+        },
+        analysis.early_hits,
+        analysis.late_hits,
+    );
+
+    let [n300, n100, n50, misses] = analysis.judgement_counts();
+
+    let judgements = format!(
+        "{n300}/{n100}/{n50}/{misses}\n(replay: {}/{}/{}/{})",
+        replay.count_300, replay.count_100, replay.count_50, replay.count_miss
+    );
+
+    let mut sections = String::new();
+
+    for section in analysis.sections.iter() {
+        let _ = write!(
+            sections,
+            "`{} - {}` {:.2}%",
+            format_time(section.start),
+            format_time(section.end),
+            section.accuracy
+        );
+
+        match section.misses {
+            0 => sections.push('\n'),
+            1 => sections.push_str(" • 1 miss\n"),
+            n => {
+                let _ = writeln!(sections, " • {n} misses");
+            }
+        }
+    }
+
+    if sections.is_empty() {
+        sections.push_str("The map has no objects");
+    }
+
+    let keys = &analysis.keys;
+    let presses = (keys.k1 + keys.k2 + keys.m1 + keys.m2).max(1) as f64;
+
+    let key_usage = [
+        ("K1", keys.k1),
+        ("K2", keys.k2),
+        ("M1", keys.m1),
+        ("M2", keys.m2),
+    ]
+    .into_iter()
+    .filter(|(_, count)| *count > 0)
+    .map(|(key, count)| format!("{key} {:.1}%", count as f64 / presses * 100.0))
+    .collect::<Vec<_>>()
+    .join(" • ");
+
+    let cursor_speed = format!(
+        "{:.0} px/s on average\n{:.0} px/s at the peak",
+        analysis.average_cursor_speed, analysis.peak_cursor_speed
+    );
+
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Replay analysis")
+                    .description(description)
+                    .field("Hit error", hit_error, false)
+                    .field("Judgements", judgements, true)
+                    .field("Slider breaks", analysis.slider_breaks, true)
+                    .field("Accuracy by section", sections, false)
+                    .field(
+                        "Keys",
+                        if key_usage.is_empty() {
+                            "-".to_owned()
+                        } else {
+                            key_usage
+                        },
+                        true,
+                    )
+                    .field("Cursor speed", cursor_speed, true)
+                    .color(Color::new(15785176))
+                    .footer(|f| {
+                        f.text(
+                            "Estimated from the replay frames, stacked objects and slider paths \
+                            are not taken into account",
+                        )
+                    })
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Format milliseconds as `m:ss`.
+fn format_time(ms: f64) -> String {
+    let secs = (ms / 1000.0).max(0.0) as u32;
+
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::channel::Message,
+    prelude::*,
+};
+
+use crate::{
+    checks::is_bot_owner, process_replays::send_error_message, status_message::StatusMessage,
+    ReplayHandler,
+};
+
+#[command]
+#[description = "Cancels replays in the queue or while they are being processed.\n\
+Without arguments all of your replays are cancelled.\n\
+Bot owners can cancel replays of other users."]
+#[usage = "[queue position] / worker [worker]"]
+#[example = "3"]
+#[example = "worker 1"]
+async fn cancel(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let author = msg.author.id;
+    let is_owner = is_bot_owner(author);
+
+    let data = ctx.data.read().await;
+    let queue = data.get::<ReplayHandler>().unwrap();
+
+    let (waiting, active) = match args.single::<String>().ok().as_deref() {
+        None => {
+            let waiting = queue.remove_waiting(|_, data| data.user == author).await;
+            let active = queue.cancel_active(|_, data| data.user == author).await;
+
+            (waiting, active)
+        }
+        Some("worker" | "w") => {
+            let worker = match args.single::<usize>() {
+                Ok(worker) => worker,
+                Err(_) => {
+                    msg.reply(ctx, "You must specify the number of the worker!")
+                        .await?;
+
+                    return Ok(());
+                }
+            };
+
+            let active = queue
+                .cancel_active(|idx, data| idx == worker && (is_owner || data.user == author))
+                .await;
+
+            (Vec::new(), active)
+        }
+        Some(position) => {
+            let position: usize = match position.parse() {
+                Ok(position) => position,
+                Err(_) => {
+                    msg.reply(ctx, "The queue position must be a number!")
+                        .await?;
+
+                    return Ok(());
+                }
+            };
+
+            let waiting = queue
+                .remove_waiting(|idx, data| idx == position && (is_owner || data.user == author))
+                .await;
+
+            (waiting, Vec::new())
+        }
+    };
+
+    drop(data);
+
+    if waiting.is_empty() && active.is_empty() {
+        let content = "There was no replay to cancel. \
+            You can only cancel your own replays, check `queue` for their position.";
+        msg.reply(ctx, content).await?;
+
+        return Ok(());
+    }
+
+    let cancelled = active
+        .iter()
+        .map(|(_, data)| data)
+        .chain(waiting.iter())
+        .collect::<Vec<_>>();
+
+    for data in cancelled.iter() {
+        let content = if data.user == author {
+            "your replay has been cancelled".to_owned()
+        } else {
+            format!("your replay has been cancelled by <@{author}>")
+        };
+
+        send_error_message(&ctx.http, data.input_channel, data.user, content).await;
+    }
+
+    // Workers update the status of active replays themselves
+    for data in waiting.iter() {
+        StatusMessage::new(Arc::clone(&ctx.http), data)
+            .await
+            .cancelled()
+            .await;
+    }
+
+    let content = match cancelled.len() {
+        1 => "Cancelled 1 replay".to_owned(),
+        n => format!("Cancelled {n} replays"),
+    };
+
+    msg.reply(ctx, content).await?;
+
+    Ok(())
+}
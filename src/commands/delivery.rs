@@ -0,0 +1,107 @@
+use std::collections::hash_map::Entry;
+
+use anyhow::{Context as AnyhowContext, Error};
+use serenity::{
+    client::Context,
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{channel::Message, id::ChannelId},
+    utils::Color,
+};
+
+use crate::{
+    checks::PERMISSIONS_CHECK,
+    server_settings::{Server, VideoDelivery},
+    ServerSettings,
+};
+
+#[command]
+#[description = "Choose how finished videos are sent in this server.\n\
+`attachment` sends the video file itself if it fits within the server's upload limit, \
+`link` always uploads it and sends a link.\n\
+Without arguments the current choice is shown."]
+#[usage = "[attachment/link]"]
+#[example = "link"]
+#[only_in(guilds)]
+#[checks(Permissions)]
+async fn delivery(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let delivery = match args.single::<String>().ok().as_deref() {
+        Some("attachment" | "attachments" | "a") => VideoDelivery::Attachment,
+        Some("link" | "links" | "l") => VideoDelivery::Link,
+        Some(_) => {
+            msg.reply(ctx, "The delivery must be either `attachment` or `link`!")
+                .await?;
+
+            return Ok(());
+        }
+        None => {
+            let data = ctx.data.read().await;
+            let settings = data.get::<ServerSettings>().unwrap();
+
+            let delivery = settings
+                .servers
+                .get(&guild_id)
+                .map_or_else(VideoDelivery::default, |server| server.delivery);
+
+            drop(data);
+
+            let content = format!("Videos are currently sent {}", describe(delivery));
+
+            msg.channel_id
+                .send_message(ctx, |m| {
+                    m.embed(|e| e.description(content).color(Color::new(15785176)))
+                })
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let mut data = ctx.data.write().await;
+    let settings = data.get_mut::<ServerSettings>().unwrap();
+
+    match settings.servers.entry(guild_id) {
+        Entry::Occupied(e) => e.into_mut().delivery = delivery,
+        Entry::Vacant(e) => {
+            let server = Server {
+                input_channel: ChannelId(0),
+                output_channel: ChannelId(0),
+                prefixes: Vec::new(),
+                delivery,
+                title_template: None,
+            };
+
+            e.insert(server);
+        }
+    }
+
+    let edited_settings =
+        serde_json::to_string(settings).context("failed to serialize server settings")?;
+
+    drop(data);
+
+    if let Err(why) = tokio::fs::write("src/server_settings.json", edited_settings).await {
+        let err = Error::new(why).context("failed to edit server specific settings");
+        warn!("{err:?}");
+    }
+
+    let content = format!("From now on videos are sent {}", describe(delivery));
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| e.description(content).color(Color::new(15785176)))
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn describe(delivery: VideoDelivery) -> &'static str {
+    match delivery {
+        VideoDelivery::Attachment => {
+            "as attachment if they fit within the upload limit and as link otherwise"
+        }
+        VideoDelivery::Link => "as link",
+    }
+}
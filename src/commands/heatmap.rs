@@ -0,0 +1,79 @@
+use std::borrow::Cow;
+
+use anyhow::Error;
+use rosu_v2::prelude::GameMods;
+use serenity::{
+    framework::standard::{macros::command, CommandResult},
+    model::{channel::AttachmentType, prelude::Message},
+    prelude::Context,
+};
+
+use crate::{
+    process_replays::{load_analyzed_replay, LoadedReplay},
+    replay_images::{cursor_heatmap, hit_error_histogram},
+};
+
+#[command]
+#[description = "**Requires Replay Attachment**\nDraw a heatmap of the cursor and a histogram \
+of the hit errors of a replay without rendering it."]
+async fn heatmap(ctx: &Context, msg: &Message) -> CommandResult {
+    let (
+        LoadedReplay {
+            replay,
+            frames,
+            metadata,
+            ..
+        },
+        analysis,
+    ) = match load_analyzed_replay(ctx, msg, "visualize").await? {
+        Some(analyzed) => analyzed,
+        None => return Ok(()),
+    };
+
+    let images = cursor_heatmap(&frames)
+        .and_then(|heatmap| hit_error_histogram(&analysis).map(|histogram| (heatmap, histogram)));
+
+    let (heatmap, histogram) = match images {
+        Ok(images) => images,
+        Err(why) => {
+            let err = Error::new(why).context("failed to draw replay images");
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(());
+        }
+    };
+
+    let mods = match GameMods::from_bits(replay.mods.bits()).unwrap_or_default() {
+        GameMods::NoMod => String::new(),
+        mods => format!(" +{mods}"),
+    };
+
+    let content = format!(
+        "**{} - {} [{}]**{mods} played by {}\nUR {:.2} • the histogram shows the hit windows \
+        of 300s, 100s and 50s and the mean error in red",
+        metadata.artist,
+        metadata.title,
+        metadata.version,
+        replay.player_name.as_deref().unwrap_or("unknown"),
+        analysis.unstable_rate,
+    );
+
+    let files = [
+        AttachmentType::Bytes {
+            data: Cow::Owned(heatmap),
+            filename: "heatmap.png".to_owned(),
+        },
+        AttachmentType::Bytes {
+            data: Cow::Owned(histogram),
+            filename: "hit_errors.png".to_owned(),
+        },
+    ];
+
+    msg.channel_id
+        .send_files(&ctx, files, |m| m.content(content))
+        .await?;
+
+    Ok(())
+}
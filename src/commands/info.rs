@@ -0,0 +1,289 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Error;
+use rosu_pp::Beatmap;
+use rosu_v2::prelude::{GameMode, GameMods, OsuError};
+use serenity::{
+    builder::CreateEmbed,
+    framework::standard::{macros::command, CommandResult},
+    model::{
+        application::{component::ButtonStyle, interaction::InteractionResponseType},
+        prelude::{Message, ReactionType},
+    },
+    prelude::Context,
+    utils::Color,
+};
+use tokio::fs;
+
+use crate::{
+    announcement::format_duration,
+    play_stats::{accuracy, PlayStats},
+    process_replays::{
+        collect_replay, command_destination, format_secs, queue_replay, replay_length,
+        validate_replay_map, Data, ReplayRejection,
+    },
+    title_template::title_template,
+    util::download_osu_file,
+    HttpClient, OsuClient, ReplayHandler,
+};
+
+const RENDER_BUTTON: &str = "render_replay";
+
+/// How long the author has to decide whether the replay should be rendered.
+const BUTTON_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[command]
+#[description = "**Requires Replay Attachment**\nShow everything about a replay without queueing it.\n\
+Press the ▶️ button afterwards to render it."]
+async fn info(ctx: &Context, msg: &Message) -> CommandResult {
+    let file = match collect_replay(ctx, msg, "check").await? {
+        Some(file) => file,
+        None => return Ok(()),
+    };
+
+    let replay = &file.replay;
+
+    let hash = match replay.beatmap_hash.as_deref() {
+        Some(hash) => hash,
+        None => {
+            msg.reply(&ctx, "Couldn't find the map's hash in your replay file")
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let (osu, client, queue) = {
+        let data = ctx.data.read().await;
+
+        (
+            Arc::clone(data.get::<OsuClient>().unwrap()),
+            data.get::<HttpClient>().unwrap().clone(),
+            Arc::clone(data.get::<ReplayHandler>().unwrap()),
+        )
+    };
+
+    let map = match osu.beatmap().checksum(hash).await {
+        Ok(map) => map,
+        Err(OsuError::NotFound) => {
+            let content = format!("Can't show the replay, {}", ReplayRejection::UnknownMap);
+            msg.reply(&ctx, content).await?;
+
+            return Ok(());
+        }
+        Err(why) => {
+            let err = Error::new(why).context(format!("failed to request map with hash `{hash}`"));
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(());
+        }
+    };
+
+    // Without the .osu file the stars without mods are still known
+    let stats = match download_osu_file(&client, map.map_id, hash).await {
+        Ok(Some(bytes)) if map.mode == GameMode::STD => {
+            match Beatmap::parse(bytes.as_slice()).await {
+                Ok(beatmap) => Some(PlayStats::new(replay, &beatmap)),
+                Err(why) => {
+                    let err = Error::new(why).context("failed to parse map of replay info");
+                    warn!("{err:?}");
+
+                    None
+                }
+            }
+        }
+        Ok(_) => None,
+        Err(why) => {
+            warn!("{:?}", why.context("failed to download map of replay info"));
+
+            None
+        }
+    };
+
+    let length = replay_length(map.seconds_drain, replay, None);
+    let estimate = queue.estimate_render(length).await;
+    let rejection = validate_replay_map(ctx, replay, &map, None).await.err();
+    let destination = command_destination(ctx, msg).await;
+
+    let mut embed = CreateEmbed::default();
+
+    let mut description = match map.mapset {
+        Some(ref mapset) => {
+            embed
+                .title(format!(
+                    "{} - {} [{}]",
+                    mapset.artist, mapset.title, map.version
+                ))
+                .thumbnail(&mapset.covers.list);
+
+            format!("Mapped by {}", mapset.creator_name)
+        }
+        None => {
+            embed.title(&map.version);
+
+            String::new()
+        }
+    };
+
+    match (&rejection, &destination) {
+        (Some(rejection), _) => {
+            description.push_str(&format!("\n⚠️ This replay can't be rendered, {rejection}"));
+        }
+        (None, None) => description
+            .push_str("\n⚠️ This server has no output channel yet, use the `setup` command first!"),
+        (None, Some(_)) => {}
+    }
+
+    let mods = GameMods::from_bits(replay.mods.bits())
+        .unwrap_or_default()
+        .to_string();
+
+    let combo = match map.max_combo {
+        Some(max_combo) => format!("{}/{max_combo}x", replay.max_combo),
+        None => format!("{}x", replay.max_combo),
+    };
+
+    let stars = stats.as_ref().map_or(map.stars as f64, |stats| stats.stars);
+
+    embed
+        .url(&map.url)
+        .description(description)
+        .color(Color::new(15785176))
+        .field(
+            "Player",
+            replay.player_name.as_deref().unwrap_or("unknown"),
+            true,
+        )
+        .field("Mods", mods, true)
+        .field(
+            "Date",
+            format!("<t:{}:f>", replay.timestamp.timestamp()),
+            true,
+        )
+        .field(
+            "Hits",
+            format!(
+                "{}/{}/{}/{}",
+                replay.count_300, replay.count_100, replay.count_50, replay.count_miss
+            ),
+            true,
+        )
+        .field(
+            "Accuracy",
+            format!("{:.2}%", accuracy(replay, GameMode::STD)),
+            true,
+        )
+        .field("Combo", combo, true)
+        .field("Stars", format!("{stars:.2}⭐"), true);
+
+    if let Some(ref stats) = stats {
+        let pp = if stats.is_full_combo() {
+            format!("{:.2}", stats.pp)
+        } else {
+            format!("{:.2} ({:.2} for FC)", stats.pp, stats.fc_pp)
+        };
+
+        embed
+            .field("Grade", stats.grade, true)
+            .field("PP", pp, true);
+    }
+
+    embed.field("Length", format_secs(length), true).field(
+        "Estimated render time",
+        format!("~{}", format_duration(estimate)),
+        true,
+    );
+
+    let destination = match destination {
+        Some(destination) if rejection.is_none() => destination,
+        _ => {
+            msg.channel_id
+                .send_message(&ctx, |m| m.set_embed(embed))
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let mut info_msg = msg
+        .channel_id
+        .send_message(&ctx, |m| {
+            m.set_embed(embed).components(|c| {
+                c.create_action_row(|row| {
+                    row.create_button(|button| {
+                        button
+                            .style(ButtonStyle::Primary)
+                            .emoji(ReactionType::Unicode("▶️".to_owned()))
+                            .label("Render")
+                            .custom_id(RENDER_BUTTON)
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let interaction = info_msg
+        .await_component_interaction(ctx)
+        .author_id(msg.author.id)
+        .filter(|interaction| interaction.data.custom_id == RENDER_BUTTON)
+        .timeout(BUTTON_TIMEOUT)
+        .await;
+
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => {
+            info_msg.edit(&ctx, |m| m.components(|c| c)).await?;
+
+            return Ok(());
+        }
+    };
+
+    // The button is removed so the replay can't be queued twice
+    interaction
+        .create_interaction_response(&ctx, |r| {
+            r.kind(InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.components(|c| c))
+        })
+        .await?;
+
+    let path = format!("../Downloads/{}_0_{}", msg.id, file.name);
+
+    if let Err(why) = fs::write(&path, &file.bytes).await {
+        let err = Error::new(why).context(format!("failed writing to `{path}`"));
+        warn!("{err:?}");
+
+        msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+        return Ok(());
+    }
+
+    let (guild, output_channel, delivery) = destination;
+
+    let replay_data = Data {
+        guild,
+        input_channel: msg.channel_id,
+        output_channel,
+        path,
+        replay: file.replay,
+        time_points: None,
+        user: msg.author.id,
+        delivery,
+        score: None,
+        knockout: Vec::new(),
+        title_template: title_template(ctx, guild, msg.author.id).await,
+        status_message: None,
+    };
+
+    queue_replay(ctx, replay_data).await;
+
+    let reaction = ReactionType::Unicode("✅".to_string());
+
+    if let Err(why) = msg.react(ctx, reaction).await {
+        let err = Error::new(why).context("failed to react after queueing replay from info");
+        warn!("{err:?}");
+    }
+
+    Ok(())
+}
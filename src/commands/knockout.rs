@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+
+use anyhow::Error;
+use serenity::{
+    framework::standard::{macros::command, CommandResult},
+    model::prelude::{Message, ReactionType},
+    prelude::Context,
+};
+use tokio::fs;
+
+use crate::{
+    process_replays::{
+        collect_replay_files, command_destination, queue_replay, validate_replay,
+        AttachmentParseError, Data,
+    },
+    title_template::title_template,
+};
+
+/// danser struggles to render more players than this.
+const MAX_KNOCKOUT_REPLAYS: usize = 50;
+
+#[command]
+#[description = "**Requires Replay Attachments**\nRenders several replays of the same map \
+together in knockout mode.\nAttach the `.osr` files directly or as `.zip` archive. \
+The knockout can be configured through the `settings` command."]
+async fn knockout(ctx: &Context, msg: &Message) -> CommandResult {
+    let (guild, output_channel, delivery) = match command_destination(ctx, msg).await {
+        Some(destination) => destination,
+        None => {
+            let content = "This server has no output channel yet, use the `setup` command first!";
+            msg.reply(&ctx, content).await?;
+
+            return Ok(());
+        }
+    };
+
+    let mut files = match collect_replay_files(&msg.attachments, MAX_KNOCKOUT_REPLAYS).await {
+        Ok(files) => files,
+        Err(
+            why @ (AttachmentParseError::TooManyReplays(_) | AttachmentParseError::TooLarge(_)),
+        ) => {
+            msg.reply(&ctx, format!("A knockout can't be rendered, {why}"))
+                .await?;
+
+            return Ok(());
+        }
+        Err(AttachmentParseError::IncorrectMode(_)) => {
+            msg.reply(&ctx, "danser only accepts osu!standard plays, sorry :(")
+                .await?;
+
+            return Ok(());
+        }
+        Err(AttachmentParseError::Parsing(_) | AttachmentParseError::Archive(_)) => {
+            msg.reply(&ctx, "One of the attachments could not be read")
+                .await?;
+
+            return Ok(());
+        }
+        Err(why) => {
+            let err = Error::new(why).context("failed to collect knockout replays");
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(());
+        }
+    };
+
+    if files.len() < 2 {
+        msg.reply(&ctx, "A knockout needs at least two replays!")
+            .await?;
+
+        return Ok(());
+    }
+
+    let first = &files[0];
+
+    if let Some(other) = files
+        .iter()
+        .find(|file| file.replay.beatmap_hash != first.replay.beatmap_hash)
+    {
+        let content = format!(
+            "All replays must be played on the same map but `{}` and `{}` are not",
+            first.name, other.name
+        );
+
+        msg.reply(&ctx, content).await?;
+
+        return Ok(());
+    }
+
+    let mut checked_mods = HashSet::new();
+
+    // The map is the same for all replays so only the mods affect the validation
+    for file in files
+        .iter()
+        .filter(|file| checked_mods.insert(file.replay.mods.bits()))
+    {
+        if let Err(rejection) = validate_replay(ctx, &file.replay, None).await {
+            let content = format!("`{}` can't be rendered, {rejection}", file.name);
+            msg.reply(&ctx, content).await?;
+
+            return Ok(());
+        }
+    }
+
+    let mut paths = Vec::with_capacity(files.len());
+
+    // Replays of different players may share a file name
+    for (i, file) in files.iter().enumerate() {
+        let path = format!("../Downloads/{}_{i}_{}", msg.id, file.name);
+
+        if let Err(why) = fs::write(&path, &file.bytes).await {
+            let err = Error::new(why).context(format!("failed writing to `{path}`"));
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(());
+        }
+
+        paths.push(path);
+    }
+
+    let path = paths.remove(0);
+    let replay = files.swap_remove(0).replay;
+
+    let replay_data = Data {
+        guild,
+        input_channel: msg.channel_id,
+        output_channel,
+        path,
+        replay,
+        time_points: None,
+        user: msg.author.id,
+        delivery,
+        score: None,
+        knockout: paths,
+        title_template: title_template(ctx, guild, msg.author.id).await,
+        status_message: None,
+    };
+
+    queue_replay(ctx, replay_data).await;
+
+    let reaction = ReactionType::Unicode("✅".to_string());
+
+    if let Err(why) = msg.react(&ctx, reaction).await {
+        let err = Error::new(why).context("failed to react after queueing knockout");
+        warn!("{err:?}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::channel::Message,
+    prelude::Context,
+    utils::Color,
+};
+
+use crate::{score_replay::find_player, OsuClient, OsuLinkHandler};
+
+#[command]
+#[description = "Link your osu! account so `recent` and `top` work without a username.\n\
+Without arguments the currently linked account is shown."]
+#[usage = "[osu! username]"]
+#[example = "mezo"]
+async fn link(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let name = args.rest().trim();
+
+    let content = if name.is_empty() {
+        let (osu, linked) = {
+            let data = ctx.data.read().await;
+            let osu = Arc::clone(data.get::<OsuClient>().unwrap());
+
+            (
+                osu,
+                data.get::<OsuLinkHandler>().unwrap().get(msg.author.id),
+            )
+        };
+
+        match linked {
+            Some(user_id) => match osu.user(user_id).await {
+                Ok(user) => format!("You are linked to **{}**", user.username),
+                Err(_) => format!("You are linked to the osu! user with id {user_id}"),
+            },
+            None => "You have not linked an osu! account yet".to_owned(),
+        }
+    } else {
+        let user = match find_player(ctx, msg, name).await? {
+            Some(user) => user,
+            None => return Ok(()),
+        };
+
+        // Writing the file happens after releasing the lock so other commands aren't blocked
+        let links = {
+            let mut data = ctx.data.write().await;
+            let links = data.get_mut::<OsuLinkHandler>().unwrap();
+            links.insert(msg.author.id, user.user_id);
+
+            links.clone()
+        };
+
+        links.persist().await;
+
+        format!("You are now linked to **{}**", user.username)
+    };
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| e.description(content).color(Color::new(15785176)))
+        })
+        .await?;
+
+    Ok(())
+}
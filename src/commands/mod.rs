@@ -22,11 +22,44 @@ pub use setup::*;
 mod queue;
 pub use queue::*;
 
-mod start;
-pub use start::*;
-
-mod end;
-pub use end::*;
+mod trim;
+pub use trim::*;
 
 mod addskin;
-pub use addskin::*;
\ No newline at end of file
+pub use addskin::*;
+
+mod cancel;
+pub use cancel::*;
+
+mod delivery;
+pub use delivery::*;
+
+mod render;
+pub use render::*;
+
+mod link;
+pub use link::*;
+
+mod recent;
+pub use recent::*;
+
+mod top;
+pub use top::*;
+
+mod knockout;
+pub use knockout::*;
+
+mod title;
+pub use title::*;
+
+mod servertitle;
+pub use servertitle::*;
+
+mod analyze;
+pub use analyze::*;
+
+mod heatmap;
+pub use heatmap::*;
+
+mod info;
+pub use info::*;
@@ -8,7 +8,10 @@ use serenity::{
 };
 use std::{collections::hash_map::Entry, fmt::Write};
 
-use crate::{server_settings::Server, ServerSettings, DEFAULT_PREFIX};
+use crate::{
+    server_settings::{Server, VideoDelivery},
+    ServerSettings, DEFAULT_PREFIX,
+};
 
 #[command]
 #[description = "Adjust prefixes in a server"]
@@ -33,6 +36,8 @@ async fn prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
                         input_channel: ChannelId(0),
                         output_channel: ChannelId(0),
                         prefixes: Vec::new(),
+                        delivery: VideoDelivery::default(),
+                        title_template: None,
                     };
 
                     &mut e.insert(server).prefixes
@@ -18,26 +18,25 @@ async fn queue(ctx: &Context, msg: &Message) -> CommandResult {
     let data_guard = ctx.data.read().await;
     let queue_guard = data_guard.get::<ReplayHandler>().unwrap();
     let inner_queue_guard = queue_guard.queue.lock().await;
+    let active_guard = queue_guard.active.lock().await;
 
-    let queue_list = if inner_queue_guard.is_empty() {
+    let queue_list = if inner_queue_guard.is_empty() && active_guard.is_empty() {
         "The queue is empty".to_string()
     } else {
         let mut s = String::new();
 
-        let status = *queue_guard.status.lock().await;
+        for (worker, active) in active_guard.iter() {
+            let name = active.data.name();
+            let user = active.data.user;
+            let status = active.status;
 
-        for (replay_data, idx) in inner_queue_guard.iter().zip(1..) {
-            let name = replay_data
-                .path
-                .replace("../Downloads/", "")
-                .replace('_', " ")
-                .replace(".osr", "");
-
-            let status = (idx == 1)
-                .then_some(status)
-                .unwrap_or(ReplayStatus::Waiting);
+            let _ = writeln!(s, "Worker {worker}: {name} queued by <@{user}> - {status}");
+        }
 
+        for (replay_data, idx) in inner_queue_guard.iter().zip(1..) {
+            let name = replay_data.name();
             let user = replay_data.user;
+            let status = ReplayStatus::Waiting;
 
             let _ = writeln!(s, "{idx}. {name} queued by <@{user}> - {status}");
         }
@@ -45,6 +44,9 @@ async fn queue(ctx: &Context, msg: &Message) -> CommandResult {
         s
     };
 
+    drop(active_guard);
+    drop(inner_queue_guard);
+
     msg.channel_id
         .send_message(&ctx, |m| {
             m.reference_message((msg.channel_id, msg.id))
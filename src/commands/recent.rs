@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use anyhow::Error;
+use rosu_v2::prelude::GameMode;
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{
+    score_replay::{find_player, queue_score_replay},
+    OsuClient,
+};
+
+#[command]
+#[description = "Render the replay of a player's most recent passed osu!standard score.\n\
+Without a username your linked account is used."]
+#[usage = "[osu! username]"]
+#[example = "mezo"]
+#[aliases("rs")]
+async fn recent(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let user = match find_player(ctx, msg, args.rest().trim()).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let osu = Arc::clone(ctx.data.read().await.get::<OsuClient>().unwrap());
+
+    let scores_fut = osu
+        .user_scores(user.user_id)
+        .recent()
+        .mode(GameMode::STD)
+        .limit(1);
+
+    let score = match scores_fut.await {
+        Ok(scores) => scores.into_iter().next(),
+        Err(why) => {
+            let err = Error::new(why).context("failed to request recent scores");
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(());
+        }
+    };
+
+    let score = match score {
+        Some(score) => score,
+        None => {
+            let content = format!(
+                "**{}** has no passed osu!standard scores in the last 24 hours",
+                user.username
+            );
+
+            msg.reply(&ctx, content).await?;
+
+            return Ok(());
+        }
+    };
+
+    let description = format!("the most recent score of **{}**", user.username);
+
+    queue_score_replay(ctx, msg, score, &description).await
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use anyhow::Error;
+use rosu_v2::prelude::{GameMode, OsuError};
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{
+    score_replay::{parse_score_id, queue_score_replay},
+    OsuClient,
+};
+
+#[command]
+#[description = "Render the replay of an osu!standard score from the website"]
+#[usage = "[score id / score url]"]
+#[example = "4088392361"]
+#[example = "https://osu.ppy.sh/scores/osu/4088392361"]
+async fn render(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let score_id = match args.single::<String>().ok().as_deref().map(parse_score_id) {
+        Some(Ok(score_id)) => score_id,
+        Some(Err(content)) => {
+            msg.reply(&ctx, content).await?;
+
+            return Ok(());
+        }
+        None => {
+            msg.reply(&ctx, "You must enter a score id or a score url!")
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let osu = Arc::clone(ctx.data.read().await.get::<OsuClient>().unwrap());
+
+    let score = match osu.score(score_id, GameMode::STD).await {
+        Ok(score) => score,
+        Err(OsuError::NotFound) => {
+            msg.reply(&ctx, "There is no osu!standard score with that id")
+                .await?;
+
+            return Ok(());
+        }
+        Err(why) => {
+            let err = Error::new(why).context(format!("failed to request score {score_id}"));
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(());
+        }
+    };
+
+    queue_score_replay(ctx, msg, score, "that score").await
+}
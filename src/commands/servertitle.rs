@@ -0,0 +1,89 @@
+use std::collections::hash_map::Entry;
+
+use anyhow::{Context as AnyhowContext, Error};
+use serenity::{
+    client::Context,
+    framework::standard::{macros::command, Args, CommandResult},
+    model::{channel::Message, id::ChannelId},
+};
+
+use super::title::send_template;
+use crate::{
+    checks::PERMISSIONS_CHECK,
+    server_settings::{Server, VideoDelivery},
+    title_template::TitleTemplate,
+    ServerSettings,
+};
+
+#[command]
+#[description = "Choose the title of videos in this server, users can override it with the \
+`title` command.\nThe placeholders are the same as for the `title` command.\n\
+`reset` goes back to the default title and without arguments the current title is shown."]
+#[usage = "[template/reset]"]
+#[example = "[{stars}⭐] {player} | {artist} - {title} [{difficulty}] {mods} {grade}"]
+#[example = "reset"]
+#[only_in(guilds)]
+#[checks(Permissions)]
+async fn servertitle(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild_id = msg.guild_id.unwrap();
+
+    let template = match args.rest().trim() {
+        "" => {
+            let data = ctx.data.read().await;
+            let settings = data.get::<ServerSettings>().unwrap();
+
+            let template = settings
+                .servers
+                .get(&guild_id)
+                .and_then(|server| server.title_template.clone());
+
+            drop(data);
+
+            let heading = "Videos in this server are titled";
+
+            return send_template(ctx, msg, heading, template.as_deref()).await;
+        }
+        "reset" => None,
+        input => {
+            if let Err(why) = TitleTemplate::validate(input) {
+                msg.reply(ctx, format!("Invalid title, {why}")).await?;
+
+                return Ok(());
+            }
+
+            Some(input.to_owned())
+        }
+    };
+
+    let mut data = ctx.data.write().await;
+    let settings = data.get_mut::<ServerSettings>().unwrap();
+
+    match settings.servers.entry(guild_id) {
+        Entry::Occupied(e) => e.into_mut().title_template = template.clone(),
+        Entry::Vacant(e) => {
+            let server = Server {
+                input_channel: ChannelId(0),
+                output_channel: ChannelId(0),
+                prefixes: Vec::new(),
+                delivery: VideoDelivery::default(),
+                title_template: template.clone(),
+            };
+
+            e.insert(server);
+        }
+    }
+
+    let edited_settings =
+        serde_json::to_string(settings).context("failed to serialize server settings")?;
+
+    drop(data);
+
+    if let Err(why) = tokio::fs::write("src/server_settings.json", edited_settings).await {
+        let err = Error::new(why).context("failed to edit server specific settings");
+        warn!("{err:?}");
+    }
+
+    let heading = "From now on videos in this server are titled";
+
+    send_template(ctx, msg, heading, template.as_deref()).await
+}
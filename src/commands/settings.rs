@@ -48,7 +48,13 @@ use crate::commands::Settings;
 !!settings show_sliderbreaks `[on/off]` - adds/removes a sliderbreak counter to the hit counter
 
 **Strain Graph**
-!!settings show_strain_graph`[on/off]` - enable/disable the strain graph"]
+!!settings show_strain_graph`[on/off]` - enable/disable the strain graph
+
+**Knockout**
+!!settings knockout_mode `[combobreak/maxcombo/xreplays/onevsone/ssorquit]` - changes when players are knocked out
+!!settings knockout_max_players `[1 - 50]` - changes how many players are shown at once
+!!settings knockout_revive `[on/off]` - enable/disable reviving all players at the end
+!!settings knockout_live_sort `[on/off]` - enable/disable sorting the players while playing"]
 #[usage = "[setting] [value]\nsettings [user]\nsettings copy [user]"]
 async fn settings(ctx: &SerenityContext, msg: &Message) -> CommandResult {
     let author = msg.mentions.first().map_or(msg.author.id, |user| user.id);
@@ -138,7 +144,8 @@ async fn settings(ctx: &SerenityContext, msg: &Message) -> CommandResult {
                         **Hit Error Meter**\n`show hit error meter`: {}\n`hit error decimals`: {}\n\n\
                         **Aim Error Meter**\n`show aim error meter`: {}\n`aim error meter ur decimals`: {}\n\n\
                         **Hit Counter**\n`show hit counter`: {}\n`show sliderbreaks`: {}\n\n\
-                        **Strain Graph**\n`show strain graph`: {}",
+                        **Strain Graph**\n`show strain graph`: {}\n\n\
+                        **Knockout**\n`knockout mode`: {}\n`knockout max players`: {}\n`knockout revive`: {}\n`knockout live sort`: {}",
                         settings.skin.current_skin,
                         settings.skin.cursor.scale,
                         if settings.cursor.cursor_ripples {
@@ -201,6 +208,18 @@ async fn settings(ctx: &SerenityContext, msg: &Message) -> CommandResult {
                             "on"
                         } else {
                             "off"
+                        },
+                        knockout_mode_name(settings.knockout.mode),
+                        settings.knockout.max_players,
+                        if settings.knockout.revive_players_at_end {
+                            "on"
+                        } else {
+                            "off"
+                        },
+                        if settings.knockout.live_sort {
+                            "on"
+                        } else {
+                            "off"
                         }
                     ))
                     .color(Color::new(15785176))
@@ -215,6 +234,17 @@ async fn path_exists(path: String) -> bool {
     fs::metadata(path).await.is_ok()
 }
 
+/// Names of danser's knockout modes, indexed by their value in the settings file.
+const KNOCKOUT_MODES: [&str; 5] = ["combobreak", "maxcombo", "xreplays", "onevsone", "ssorquit"];
+
+fn knockout_mode_name(mode: i64) -> &'static str {
+    usize::try_from(mode)
+        .ok()
+        .and_then(|mode| KNOCKOUT_MODES.get(mode))
+        .copied()
+        .unwrap_or("unknown")
+}
+
 #[derive(Debug, thiserror::Error)]
 enum EditSettingsError {
     #[error("Aim error meter ur decimals have to be between 0 and 3!")]
@@ -227,6 +257,12 @@ enum EditSettingsError {
     InvalidHitErrorDecimals,
     #[error("Hitsound volume has to be between 0 and 100!")]
     InvalidHitsoundVolume,
+    #[error(
+        "Knockout mode has to be one of `combobreak`, `maxcombo`, `xreplays`, `onevsone` or `ssorquit`!"
+    )]
+    InvalidKnockoutMode,
+    #[error("Knockout max players have to be between 1 and 50!")]
+    InvalidKnockoutPlayers,
     #[error("Music volume has to be between 0 and 100!")]
     InvalidMusicVolume,
     #[error("PP counter decimals have to be between 0 and 3!")]
@@ -397,6 +433,32 @@ async fn edit_setting(
             settings.gameplay.strain_graph.show =
                 matches!(value.to_uppercase().as_str(), "ON" | "TRUE" | "YES");
         }
+        "knockout_mode" | "knockoutmode" => {
+            let mode = KNOCKOUT_MODES
+                .iter()
+                .position(|mode| mode.eq_ignore_ascii_case(value))
+                .ok_or(EditSettingsError::InvalidKnockoutMode)?;
+
+            settings.knockout.mode = mode as i64;
+        }
+        "knockout_max_players" | "knockoutmaxplayers" => {
+            let value_as_number: i64 =
+                value.parse().map_err(|_| EditSettingsError::InvalidValue)?;
+
+            if value_as_number < 1 || value_as_number > 50 {
+                return Err(EditSettingsError::InvalidKnockoutPlayers);
+            }
+
+            settings.knockout.max_players = value_as_number;
+        }
+        "knockout_revive" | "knockoutrevive" => {
+            settings.knockout.revive_players_at_end =
+                matches!(value.to_uppercase().as_str(), "ON" | "TRUE" | "YES");
+        }
+        "knockout_live_sort" | "knockoutlivesort" => {
+            settings.knockout.live_sort =
+                matches!(value.to_uppercase().as_str(), "ON" | "TRUE" | "YES");
+        }
         _ => {
             return Err(EditSettingsError::InvalidSetting);
         }
@@ -1,7 +1,10 @@
 use std::collections::hash_map::Entry;
 
 use crate::checks::PERMISSIONS_CHECK;
-use crate::{server_settings::Server, ServerSettings};
+use crate::{
+    server_settings::{Server, VideoDelivery},
+    ServerSettings,
+};
 use anyhow::{Context, Error};
 use serenity::builder::ParseValue;
 use serenity::utils::Color;
@@ -41,6 +44,8 @@ async fn setup(ctx: &SerenityContext, msg: &Message) -> CommandResult {
                     input_channel: id1,
                     output_channel: id2,
                     prefixes: Vec::new(),
+                    delivery: VideoDelivery::default(),
+                    title_template: None,
                 });
 
             serde_json::to_string(settings).context("failed to serialize server settings")?
@@ -67,7 +72,7 @@ async fn setup(ctx: &SerenityContext, msg: &Message) -> CommandResult {
                         e.title(format!(
                             "Current channel setup{}",
                             if let Some(guild) = msg.guild_id {
-                                format!(" for {}", guild.name(&ctx).unwrap_or_default())
+                                format!(" for {}", guild.name(ctx).unwrap_or_default())
                             } else {
                                 String::new()
                             }
@@ -0,0 +1,90 @@
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::channel::Message,
+    prelude::Context,
+    utils::Color,
+};
+
+use crate::{
+    title_template::{title_template, TitleTemplate, DEFAULT_TEMPLATE},
+    TitleTemplateHandler,
+};
+
+#[command]
+#[description = "Choose the title of videos of your replays, overriding the server's choice.\n\
+Placeholders like `{player}` are replaced for each replay, available are `{player}`, `{artist}`, \
+`{title}`, `{difficulty}`, `{mods}`, `{stars}`, `{acc}`, `{pp}`, `{fcpp}`, `{combo}`, \
+`{maxcombo}`, `{misses}`, `{grade}` and `{mapper}`.\n\
+`reset` goes back to the server's title and without arguments the current title is shown."]
+#[usage = "[template/reset]"]
+#[example = "{player} | {artist} - {title} [{difficulty}] {mods} {pp}pp"]
+#[example = "reset"]
+async fn title(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let input = args.rest().trim();
+
+    let (heading, template) = match input {
+        "" => {
+            let template = title_template(ctx, msg.guild_id, msg.author.id).await;
+
+            ("Your videos are titled", template)
+        }
+        "reset" => {
+            set_template(ctx, msg, None).await;
+
+            let template = title_template(ctx, msg.guild_id, msg.author.id).await;
+
+            ("From now on your videos are titled", template)
+        }
+        input => {
+            if let Err(why) = TitleTemplate::validate(input) {
+                msg.reply(ctx, format!("Invalid title, {why}")).await?;
+
+                return Ok(());
+            }
+
+            set_template(ctx, msg, Some(input.to_owned())).await;
+
+            ("From now on your videos are titled", Some(input.to_owned()))
+        }
+    };
+
+    send_template(ctx, msg, heading, template.as_deref()).await
+}
+
+async fn set_template(ctx: &Context, msg: &Message, template: Option<String>) {
+    // Writing the file happens after releasing the lock so other commands aren't blocked
+    let templates = {
+        let mut data = ctx.data.write().await;
+        let templates = data.get_mut::<TitleTemplateHandler>().unwrap();
+        templates.set(msg.author.id, template);
+
+        templates.clone()
+    };
+
+    templates.persist().await;
+}
+
+/// Show the template and how a title of it looks like.
+pub(super) async fn send_template(
+    ctx: &Context,
+    msg: &Message,
+    heading: &str,
+    template: Option<&str>,
+) -> CommandResult {
+    let template = template.unwrap_or(DEFAULT_TEMPLATE);
+
+    // Stored templates were validated when they were set
+    let preview = TitleTemplate::validate(template)
+        .map(|(_, preview)| preview)
+        .unwrap_or_default();
+
+    let content = format!("{heading}\n`{template}`\n\n**Preview**\n{preview}");
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|e| e.description(content).color(Color::new(15785176)))
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use anyhow::Error;
+use rosu_v2::prelude::GameMode;
+use serenity::{
+    framework::standard::{macros::command, Args, CommandResult},
+    model::prelude::Message,
+    prelude::Context,
+};
+
+use crate::{
+    score_replay::{find_player, queue_score_replay},
+    OsuClient,
+};
+
+#[command]
+#[description = "Render the replay of a player's n-th best osu!standard score, defaults to the best one.\n\
+Without a username your linked account is used. \
+Usernames that consist of digits need an index after them."]
+#[usage = "[osu! username] [1-100]"]
+#[example = "mezo 3"]
+#[example = "mezo"]
+#[example = "3"]
+#[example = "mezo #3"]
+async fn top(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let (name, index) = parse_args(args.rest());
+
+    let index = match index {
+        Some(index @ 1..=100) => index,
+        Some(_) | None => {
+            msg.reply(&ctx, "The index must be between 1 and 100!")
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let user = match find_player(ctx, msg, &name).await? {
+        Some(user) => user,
+        None => return Ok(()),
+    };
+
+    let osu = Arc::clone(ctx.data.read().await.get::<OsuClient>().unwrap());
+
+    let scores_fut = osu
+        .user_scores(user.user_id)
+        .best()
+        .mode(GameMode::STD)
+        .offset(index - 1)
+        .limit(1);
+
+    let score = match scores_fut.await {
+        Ok(scores) => scores.into_iter().next(),
+        Err(why) => {
+            let err = Error::new(why).context("failed to request top scores");
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(());
+        }
+    };
+
+    let score = match score {
+        Some(score) => score,
+        None => {
+            let content = format!(
+                "**{}** doesn't have {index} osu!standard top scores",
+                user.username
+            );
+
+            msg.reply(&ctx, content).await?;
+
+            return Ok(());
+        }
+    };
+
+    let description = format!("the #{index} top score of **{}**", user.username);
+
+    queue_score_replay(ctx, msg, score, &description).await
+}
+
+/// The username and the index which is the last argument, the `#` in front of it is optional.
+///
+/// The index is `None` if it's not a number.
+fn parse_args(args: &str) -> (String, Option<usize>) {
+    let mut name: Vec<_> = args.split_whitespace().collect();
+
+    let index = match name.last() {
+        Some(last) => match last.strip_prefix('#') {
+            Some(value) => {
+                name.pop();
+
+                value.parse().ok()
+            }
+            None => match last.parse() {
+                Ok(value) => {
+                    name.pop();
+
+                    Some(value)
+                }
+                Err(_) => Some(1),
+            },
+        },
+        None => Some(1),
+    };
+
+    (name.join(" "), index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_name_and_index() {
+        assert_eq!(parse_args("mezo 3"), ("mezo".to_owned(), Some(3)));
+        assert_eq!(parse_args("mezo #3"), ("mezo".to_owned(), Some(3)));
+        assert_eq!(
+            parse_args("some player 12"),
+            ("some player".to_owned(), Some(12))
+        );
+        assert_eq!(
+            parse_args("some player"),
+            ("some player".to_owned(), Some(1))
+        );
+        assert_eq!(parse_args("3"), (String::new(), Some(3)));
+        assert_eq!(parse_args(""), (String::new(), Some(1)));
+        assert_eq!(parse_args("1234 1"), ("1234".to_owned(), Some(1)));
+        assert_eq!(parse_args("mezo #x"), ("mezo".to_owned(), None));
+    }
+}
@@ -0,0 +1,169 @@
+use serenity::{
+    framework::standard::{macros::command, CommandResult},
+    model::prelude::Message,
+    prelude::Context,
+    Result as SerenityResult,
+};
+
+use crate::{
+    process_replays::{
+        attachment_destination, load_analyzed_replay, load_replay, parse_attachment_replay,
+        queue_replay_files, respond_to_replay_attachments, validate_loaded_replay,
+        AttachmentParseError, LoadedReplay, ReplayFile, TimePoints,
+    },
+    trim_points::{Edge, MapTimeline, TrimPoint},
+};
+
+#[command]
+#[description = "**Requires Replay Attachment**\nAllows you to render only a part of a replay.\n\
+Times can be given as `h:mm:ss.mmm`, `m:ss`, or seconds. \
+Negative times count back from the end of the map.\n\
+Instead of a time you can also use a map's `object:N`, the `combo:N` of a full combo, \
+the first `miss` of the replay, `break:N` or `kiai:N`. \
+Negative numbers count from the end, e.g. `from=combo:-200` renders the last 200 combo. \
+These only work for a single replay."]
+#[usage = "[from=start] [to=end]"]
+#[example = "from=0:30 to=1:30"]
+#[example = "from=1:23.450"]
+#[example = "to=90"]
+#[example = "from=-30"]
+#[example = "from=kiai to=kiai"]
+#[example = "from=break:1 to=break:2"]
+#[example = "from=object:100 to=miss"]
+async fn trim(ctx: &Context, msg: &Message) -> CommandResult {
+    let mut from = None;
+    let mut to = None;
+
+    for arg in msg.content.split_whitespace().skip(1) {
+        let (point, value) = match arg.split_once('=') {
+            Some(("from", value)) => (&mut from, value),
+            Some(("to", value)) => (&mut to, value),
+            _ => {
+                let content = format!("Unknown argument `{arg}`, use `from=` and `to=`!");
+                msg.reply(&ctx, content).await?;
+
+                return Ok(());
+            }
+        };
+
+        match TrimPoint::parse(value) {
+            Ok(parsed) => *point = Some(parsed),
+            Err(content) => {
+                msg.reply(&ctx, content).await?;
+
+                return Ok(());
+            }
+        }
+    }
+
+    let points = [(from, Edge::Start), (to, Edge::End)];
+
+    if points.iter().all(|(point, _)| point.is_none()) {
+        msg.reply(&ctx, "You must enter `from=` or `to=`!").await?;
+
+        return Ok(());
+    }
+
+    let needs_map = points
+        .iter()
+        .flat_map(|(point, _)| point)
+        .any(|point| point.needs_map());
+
+    if !needs_map {
+        let time = |point: Option<TrimPoint>| match point {
+            Some(TrimPoint::Time(time)) => Some(time),
+            _ => None,
+        };
+
+        let time_points = TimePoints {
+            start: time(from),
+            end: time(to),
+        };
+
+        let result = parse_attachment_replay(ctx, msg, Some(time_points)).await;
+        respond_to_replay_attachments(ctx, msg, result).await;
+
+        return Ok(());
+    }
+
+    let destination = match attachment_destination(ctx, msg).await {
+        Some(destination) => destination,
+        None => return Ok(()),
+    };
+
+    let (loaded, time_points) = match resolve_on_map(ctx, msg, points).await? {
+        Some(resolved) => resolved,
+        None => return Ok(()),
+    };
+
+    // The replay and its map are loaded already so they're not downloaded again
+    let result = match validate_loaded_replay(ctx, &loaded, Some(time_points)).await {
+        Ok(()) => {
+            let LoadedReplay {
+                name,
+                bytes,
+                replay,
+                ..
+            } = loaded;
+
+            let files = vec![ReplayFile {
+                name,
+                bytes,
+                replay,
+            }];
+
+            queue_replay_files(ctx, msg, destination, files, Some(time_points)).await
+        }
+        Err(rejection) => Err(AttachmentParseError::Rejected {
+            name: loaded.name,
+            rejection,
+        }),
+    };
+
+    respond_to_replay_attachments(ctx, msg, result).await;
+
+    Ok(())
+}
+
+/// Resolve the points with the map of the attached replay.
+///
+/// `None` if that's not possible, in which case the author has been told why.
+async fn resolve_on_map(
+    ctx: &Context,
+    msg: &Message,
+    points: [(Option<TrimPoint>, Edge); 2],
+) -> SerenityResult<Option<(LoadedReplay, TimePoints)>> {
+    let needs_analysis = points
+        .iter()
+        .flat_map(|(point, _)| point)
+        .any(|point| point.needs_analysis());
+
+    let loaded = if needs_analysis {
+        load_analyzed_replay(ctx, msg, "trim")
+            .await?
+            .map(|(loaded, analysis)| (loaded, Some(analysis)))
+    } else {
+        load_replay(ctx, msg, "trim")
+            .await?
+            .map(|loaded| (loaded, None))
+    };
+
+    let (loaded, analysis) = match loaded {
+        Some(loaded) => loaded,
+        None => return Ok(None),
+    };
+
+    let timeline = MapTimeline::new(&loaded.map, &loaded.sections, analysis.as_ref());
+
+    let [start, end] =
+        points.map(|(point, edge)| point.map(|point| timeline.time(point, edge)).transpose());
+
+    match (start, end) {
+        (Ok(start), Ok(end)) => Ok(Some((loaded, TimePoints { start, end }))),
+        (Err(content), _) | (_, Err(content)) => {
+            msg.reply(&ctx, content).await?;
+
+            Ok(None)
+        }
+    }
+}
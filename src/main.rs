@@ -13,9 +13,15 @@ use std::{
     path::{Path, PathBuf},
     pin::Pin,
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::{Error, Result};
+use mapset_cache::MapsetCache;
+use mapset_mirror::{MirrorChain, MirrorConfig};
+use osu_links::OsuLinks;
+use pipeline::{process_replay, WorkerContext};
+use render_cache::RenderCache;
 use replay_queue::ReplayQueue;
 use rosu_v2::Osu;
 use serenity::{
@@ -27,13 +33,30 @@ use serenity::{
     model::prelude::*,
     prelude::*,
 };
+use title_template::UserTemplates;
+use upload_backend::DiscordAttachment;
+use util::ReplayApi;
 
+mod announcement;
 mod checks;
 mod commands;
 mod logging;
+mod mapset_cache;
+mod mapset_mirror;
+mod osu_links;
+mod pipeline;
+mod play_stats;
 mod process_replays;
+mod render_cache;
+mod replay_analysis;
+mod replay_images;
 mod replay_queue;
+mod score_replay;
 mod server_settings;
+mod status_message;
+mod title_template;
+mod trim_points;
+mod upload_backend;
 mod util;
 
 use commands::*;
@@ -41,16 +64,54 @@ use process_replays::*;
 
 const DEFAULT_PREFIX: &str = "!!";
 
+const COMMANDS_WITH_ATTACHMENTS: [&str; 6] =
+    ["trim", "knockout", "addskin", "analyze", "heatmap", "info"];
+
 struct ReplayHandler;
 impl TypeMapKey for ReplayHandler {
     type Value = Arc<ReplayQueue>;
 }
 
+struct OsuClient;
+impl TypeMapKey for OsuClient {
+    type Value = Arc<Osu>;
+}
+
+struct HttpClient;
+impl TypeMapKey for HttpClient {
+    type Value = reqwest::Client;
+}
+
+struct OsuLinkHandler;
+impl TypeMapKey for OsuLinkHandler {
+    type Value = OsuLinks;
+}
+
+struct ReplayApiHandler;
+impl TypeMapKey for ReplayApiHandler {
+    type Value = Arc<ReplayApi>;
+}
+
+struct RenderCacheHandler;
+impl TypeMapKey for RenderCacheHandler {
+    type Value = Arc<RenderCache>;
+}
+
+struct MaxReplayLength;
+impl TypeMapKey for MaxReplayLength {
+    type Value = u32;
+}
+
 struct ServerSettings;
 impl TypeMapKey for ServerSettings {
     type Value = server_settings::Root;
 }
 
+struct TitleTemplateHandler;
+impl TypeMapKey for TitleTemplateHandler {
+    type Value = UserTemplates;
+}
+
 struct Handler;
 #[async_trait]
 impl EventHandler for Handler {
@@ -64,38 +125,24 @@ impl EventHandler for Handler {
     }
 
     async fn message(&self, ctx: Context, msg: Message) {
-        if msg.content.contains("start") || msg.content.contains("end") {
-            return;
-        }
-
-        match parse_attachment_replay(&msg, &ctx.data, None).await {
-            Ok(AttachmentParseSuccess::NothingToDo) => {}
-            Ok(AttachmentParseSuccess::BeingProcessed) => {
-                let reaction = ReactionType::Unicode("✅".to_string());
-                if let Err(why) = msg.react(&ctx, reaction).await {
-                    let err =
-                        Error::new(why).context("failed to react after attachment parse success");
-                    warn!("{:?}", err);
-                }
-            }
-            Err(AttachmentParseError::IncorrectMode(_)) => {
-                if let Err(why) = msg.reply(&ctx, "danser only accepts osu!standard plays, sorry :(").await {
-                    let err =
-                        Error::new(why).context("failed to reply after attachment parse error");
-                    warn!("{:?}", err);
-                }
-            }
-            Err(why) => {
-                let err = Error::new(why).context("failed to parse attachment");
-                warn!("{:?}", err);
-                
-                if let Err(why) = msg.reply(&ctx, "something went wrong, blame mezo").await {
-                    let err =
-                        Error::new(why).context("failed to reply after attachment parse error");
-                    warn!("{:?}", err);
-                }
+        // These commands handle their attachments themselves
+        let prefix = dynamic_prefix(&ctx, &msg)
+            .await
+            .unwrap_or_else(|| DEFAULT_PREFIX.to_owned());
+
+        let command = msg
+            .content
+            .strip_prefix(prefix.as_str())
+            .and_then(|args| args.split_whitespace().next());
+
+        if let Some(command) = command {
+            if COMMANDS_WITH_ATTACHMENTS.contains(&command) {
+                return;
             }
         }
+
+        let result = parse_attachment_replay(&ctx, &msg, None).await;
+        respond_to_replay_attachments(&ctx, &msg, result).await;
     }
 
     async fn guild_create(&self, ctx: Context, _: Guild, is_new: bool) {
@@ -122,7 +169,26 @@ impl EventHandler for Handler {
 struct General;
 
 #[group]
-#[commands(settings, skinlist, addskin, setup, queue, start, end)]
+#[commands(
+    settings,
+    skinlist,
+    addskin,
+    setup,
+    queue,
+    trim,
+    cancel,
+    delivery,
+    render,
+    link,
+    recent,
+    top,
+    knockout,
+    title,
+    servertitle,
+    analyze,
+    heatmap,
+    info
+)]
 struct Danser;
 
 #[tokio::main]
@@ -145,6 +211,44 @@ async fn main() {
     let client_secret: String =
         env::var("CLIENT_SECRET").expect("Expected client secret from the env");
 
+    let workers: usize = match env::var("RENDER_WORKERS").as_deref() {
+        Ok("") | Err(_) => 1,
+        Ok(workers) => match workers.parse() {
+            Ok(workers @ 1..) => workers,
+            _ => panic!("Expected RENDER_WORKERS to be a positive integer"),
+        },
+    };
+
+    let mapset_cache_mb: u64 = match env::var("MAPSET_CACHE_MB").as_deref() {
+        Ok("") | Err(_) => 10_240,
+        Ok(size) => size
+            .parse()
+            .expect("Expected MAPSET_CACHE_MB to be an integer"),
+    };
+
+    let render_timeout = match env::var("RENDER_TIMEOUT_SECS").as_deref() {
+        Ok("") | Err(_) => Duration::from_secs(30 * 60),
+        Ok(secs) => secs
+            .parse()
+            .map(Duration::from_secs)
+            .expect("Expected RENDER_TIMEOUT_SECS to be an integer"),
+    };
+
+    let max_replay_length: u32 = match env::var("MAX_REPLAY_LENGTH_SECS").as_deref() {
+        Ok("") | Err(_) => 15 * 60,
+        Ok(secs) => secs
+            .parse()
+            .expect("Expected MAX_REPLAY_LENGTH_SECS to be an integer"),
+    };
+
+    let max_upload_mb: Option<u64> = match env::var("MAX_UPLOAD_MB").as_deref() {
+        Ok("") | Err(_) => None,
+        Ok(size) => Some(
+            size.parse()
+                .expect("Expected MAX_UPLOAD_MB to be an integer"),
+        ),
+    };
+
     let framework = StandardFramework::new()
         .configure(|c| {
             c.with_whitespace(true)
@@ -186,6 +290,16 @@ async fn main() {
         ),
     };
 
+    let http_client = reqwest_client.clone();
+
+    let replay_api = match env::var("OSU_API_KEY").as_deref() {
+        Ok("") | Err(_) => None,
+        Ok(api_key) => Some(Arc::new(ReplayApi::new(
+            http_client.clone(),
+            api_key.to_owned(),
+        ))),
+    };
+
     let settings_content = match tokio::fs::read_to_string("src/server_settings.json").await {
         Ok(content) => content,
         Err(why) => panic!(
@@ -202,18 +316,92 @@ async fn main() {
         ),
     };
 
+    let (queue, restore_notices) = match ReplayQueue::restore().await {
+        Ok(restored) => restored,
+        Err(why) => panic!("{:?}", why.context("failed to restore replay queue")),
+    };
+
     let http = Arc::clone(&client.cache_and_http.http);
-    let queue = Arc::new(ReplayQueue::new());
-    tokio::spawn(process_replay(
-        osu,
-        http,
-        reqwest_client,
-        Arc::clone(&queue),
-    ));
+
+    for notice in restore_notices {
+        send_error_message(&http, notice.channel, notice.user, notice.content).await;
+    }
+
+    let mirrors_content = match tokio::fs::read_to_string("src/mirrors.json").await {
+        Ok(content) => content,
+        Err(why) => panic!(
+            "{:?}",
+            Error::new(why).context("failed to read `src/mirrors.json`")
+        ),
+    };
+
+    let mirror_configs: Vec<MirrorConfig> = match serde_json::from_str(&mirrors_content) {
+        Ok(configs) => configs,
+        Err(why) => panic!(
+            "{:?}",
+            Error::new(why).context("failed to deserialize mirror configs")
+        ),
+    };
+
+    let mapsets = match MapsetCache::load(mapset_cache_mb * 1024 * 1024).await {
+        Ok(mapsets) => mapsets,
+        Err(why) => panic!("{:?}", why.context("failed to load mapset cache")),
+    };
+
+    let uploader = match upload_backend::backend_from_env(Arc::clone(&http)).await {
+        Ok(uploader) => uploader,
+        Err(why) => panic!("{:?}", why.context("failed to create upload backend")),
+    };
+
+    let renders = match RenderCache::load().await {
+        Ok(renders) => Arc::new(renders),
+        Err(why) => panic!("{:?}", why.context("failed to load render cache")),
+    };
+
+    let osu_links = match OsuLinks::load().await {
+        Ok(osu_links) => osu_links,
+        Err(why) => panic!("{:?}", why.context("failed to load osu! links")),
+    };
+
+    let title_templates = match UserTemplates::load().await {
+        Ok(title_templates) => title_templates,
+        Err(why) => panic!("{:?}", why.context("failed to load title templates")),
+    };
+
+    let queue = Arc::new(queue);
+    let osu = Arc::new(osu);
+
+    let worker_ctx = Arc::new(WorkerContext {
+        osu: Arc::clone(&osu),
+        http: Arc::clone(&http),
+        mirrors: Arc::new(MirrorChain::from_configs(reqwest_client, mirror_configs)),
+        queue: Arc::clone(&queue),
+        mapsets: Arc::new(mapsets),
+        renders: Arc::clone(&renders),
+        uploader,
+        attachments: DiscordAttachment::new(Arc::clone(&http)),
+        render_timeout,
+        max_upload_size: max_upload_mb.map(|mb| mb * 1024 * 1024),
+    });
+
+    for worker in 1..=workers {
+        tokio::spawn(process_replay(worker, Arc::clone(&worker_ctx)));
+    }
+
     {
         let mut data = client.data.write().await;
         data.insert::<ReplayHandler>(queue);
+        data.insert::<OsuClient>(osu);
+        data.insert::<HttpClient>(http_client);
+        data.insert::<RenderCacheHandler>(renders);
+        data.insert::<MaxReplayLength>(max_replay_length);
         data.insert::<ServerSettings>(settings);
+        data.insert::<OsuLinkHandler>(osu_links);
+        data.insert::<TitleTemplateHandler>(title_templates);
+
+        if let Some(replay_api) = replay_api {
+            data.insert::<ReplayApiHandler>(replay_api);
+        }
     }
 
     if let Err(why) = client.start().await {
@@ -243,6 +431,13 @@ async fn create_missing_folders_and_files() -> Result<()> {
             .context("failed writing to `src/server_settings.json`")?;
     }
 
+    if !Path::new("src/mirrors.json").exists() {
+        let content = serde_json::to_string_pretty(&MirrorConfig::defaults())
+            .context("failed to serialize default mirror configs")?;
+
+        fs::write("src/mirrors.json", content).context("failed writing to `src/mirrors.json`")?;
+    }
+
     Ok(())
 }
 
@@ -272,7 +467,7 @@ async fn finished_command(_: &Context, _: &Message, cmd_name: &str, cmd_result:
 fn dynamic_prefix<'fut>(
     ctx: &'fut Context,
     msg: &'fut Message,
-) -> Pin<Box<(dyn Future<Output = Option<String>> + Send + 'fut)>> {
+) -> Pin<Box<dyn Future<Output = Option<String>> + Send + 'fut>> {
     let fut = async move {
         if let Some(ref guild_id) = msg.guild_id {
             let data = ctx.data.read().await;
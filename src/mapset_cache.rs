@@ -0,0 +1,340 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs,
+    sync::{Mutex as AsyncMutex, OwnedMutexGuard},
+};
+
+use crate::util::write_atomically;
+
+const INDEX_PATH: &str = "src/mapset_cache.json";
+const SONGS_DIR: &str = "../Songs";
+
+/// Keeps track of the mapsets in `../Songs` and evicts the least recently
+/// used ones once their total size exceeds the configured budget.
+pub struct MapsetCache {
+    budget: u64,
+    state: Mutex<CacheState>,
+    /// Prevents concurrent downloads or evictions of the same mapset
+    locks: Mutex<HashMap<u32, Arc<AsyncMutex<()>>>>,
+    /// Workers use mapsets concurrently but only one of them may write the index at a time
+    persisting: AsyncMutex<()>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    mapsets: HashMap<u32, CachedMapset>,
+    /// How many jobs currently use a mapset; mapsets in use are never evicted
+    in_use: HashMap<u32, usize>,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct CachedMapset {
+    /// Size on disk in bytes
+    size: u64,
+    /// Unix timestamp in seconds
+    last_used: u64,
+}
+
+/// Marks a mapset as in use for as long as it is alive.
+pub struct MapsetGuard<'c> {
+    cache: &'c MapsetCache,
+    mapset_id: u32,
+}
+
+/// Exclusive access to the directory of a mapset, see [`MapsetCache::lock`].
+pub struct MapsetLock<'c> {
+    cache: &'c MapsetCache,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl MapsetCache {
+    /// Load the cache index and reconcile it with the mapsets that are actually on disk.
+    pub async fn load(budget: u64) -> Result<Self> {
+        let mut index: HashMap<u32, CachedMapset> = match fs::read_to_string(INDEX_PATH).await {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(index) => index,
+                // The sizes of the mapsets are measured again below
+                Err(why) => {
+                    let err = Error::new(why).context(format!(
+                        "failed to deserialize `{INDEX_PATH}`, resetting it"
+                    ));
+                    warn!("{err:?}");
+
+                    HashMap::new()
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(err) => {
+                return Err(Error::new(err).context(format!("failed to read `{INDEX_PATH}`")))
+            }
+        };
+
+        let mut mapsets = HashMap::new();
+
+        fs::create_dir_all(SONGS_DIR)
+            .await
+            .with_context(|| format!("failed to create `{SONGS_DIR}`"))?;
+
+        let mut entries = fs::read_dir(SONGS_DIR)
+            .await
+            .with_context(|| format!("failed to read dir `{SONGS_DIR}`"))?;
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .with_context(|| format!("failed to get entry of `{SONGS_DIR}`"))?
+        {
+            let mapset_id = match entry.file_name().to_str().map(str::parse) {
+                Some(Ok(mapset_id)) => mapset_id,
+                Some(Err(_)) | None => continue,
+            };
+
+            let cached = match index.remove(&mapset_id) {
+                Some(cached) => cached,
+                None => {
+                    let modified = entry
+                        .metadata()
+                        .await
+                        .ok()
+                        .and_then(|metadata| metadata.modified().ok())
+                        .map_or(0, unix_secs);
+
+                    CachedMapset {
+                        size: dir_size(entry.path()).await?,
+                        last_used: modified,
+                    }
+                }
+            };
+
+            mapsets.insert(mapset_id, cached);
+        }
+
+        let state = CacheState {
+            mapsets,
+            in_use: HashMap::new(),
+        };
+
+        let cache = Self {
+            budget,
+            state: Mutex::new(state),
+            locks: Mutex::new(HashMap::new()),
+            persisting: AsyncMutex::new(()),
+        };
+
+        cache.evict().await;
+
+        Ok(cache)
+    }
+
+    /// Mark the mapset as in use and as the most recently used one.
+    pub async fn acquire(&self, mapset_id: u32) -> MapsetGuard<'_> {
+        let is_cached = {
+            let mut state = self.state.lock().unwrap();
+            *state.in_use.entry(mapset_id).or_default() += 1;
+
+            match state.mapsets.get_mut(&mapset_id) {
+                Some(cached) => {
+                    cached.last_used = unix_secs(SystemTime::now());
+
+                    true
+                }
+                None => false,
+            }
+        };
+
+        // The order in which mapsets were used must survive restarts
+        if is_cached {
+            self.persist().await;
+        }
+
+        MapsetGuard {
+            cache: self,
+            mapset_id,
+        }
+    }
+
+    /// Wait until no one else downloads or evicts the mapset.
+    pub async fn lock(&self, mapset_id: u32) -> MapsetLock<'_> {
+        let lock = Arc::clone(self.locks.lock().unwrap().entry(mapset_id).or_default());
+
+        MapsetLock {
+            cache: self,
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+
+    /// Like [`MapsetCache::lock`] but `None` if someone else currently holds the lock.
+    fn try_lock(&self, mapset_id: u32) -> Option<MapsetLock<'_>> {
+        let mut locks = self.locks.lock().unwrap();
+        let lock = Arc::clone(locks.entry(mapset_id).or_default());
+
+        lock.try_lock_owned().ok().map(|guard| MapsetLock {
+            cache: self,
+            guard: Some(guard),
+        })
+    }
+
+    pub fn contains(&self, mapset_id: u32) -> bool {
+        self.state.lock().unwrap().mapsets.contains_key(&mapset_id)
+    }
+
+    /// Register a freshly downloaded mapset and evict old ones if the budget is exceeded.
+    pub async fn insert(&self, mapset_id: u32) -> Result<()> {
+        let size = dir_size(mapset_dir(mapset_id)).await?;
+
+        let cached = CachedMapset {
+            size,
+            last_used: unix_secs(SystemTime::now()),
+        };
+
+        self.state.lock().unwrap().mapsets.insert(mapset_id, cached);
+        self.evict().await;
+
+        Ok(())
+    }
+
+    async fn evict(&self) {
+        let mut victims = Vec::new();
+
+        {
+            let mut state = self.state.lock().unwrap();
+            let mut total: u64 = state.mapsets.values().map(|cached| cached.size).sum();
+
+            let mut candidates: Vec<_> = state
+                .mapsets
+                .iter()
+                .filter(|(mapset_id, _)| !state.in_use.contains_key(mapset_id))
+                .map(|(&mapset_id, cached)| (mapset_id, cached.clone()))
+                .collect();
+
+            candidates.sort_unstable_by_key(|(_, cached)| cached.last_used);
+            let mut candidates = candidates.into_iter();
+
+            while total > self.budget {
+                let (mapset_id, cached) = match candidates.next() {
+                    Some(candidate) => candidate,
+                    None => break,
+                };
+
+                // Skip mapsets that are currently being downloaded
+                if let Some(guard) = self.try_lock(mapset_id) {
+                    state.mapsets.remove(&mapset_id);
+                    total -= cached.size;
+                    victims.push((mapset_id, guard));
+                }
+            }
+        }
+
+        for (mapset_id, _guard) in victims {
+            let path = mapset_dir(mapset_id);
+
+            match fs::remove_dir_all(&path).await {
+                Ok(_) => info!("Evicted mapset {mapset_id} from the cache"),
+                Err(why) => {
+                    let err =
+                        Error::new(why).context(format!("failed to remove `{}`", path.display()));
+                    warn!("{err:?}");
+                }
+            }
+        }
+
+        self.persist().await;
+    }
+
+    async fn persist(&self) {
+        let _persisting = self.persisting.lock().await;
+
+        // Serialized while holding the lock so that the latest state is written last
+        let content = {
+            let state = self.state.lock().unwrap();
+
+            match serde_json::to_string(&state.mapsets) {
+                Ok(content) => content,
+                Err(why) => {
+                    let err = Error::new(why).context("failed to serialize mapset cache");
+                    warn!("{err:?}");
+
+                    return;
+                }
+            }
+        };
+
+        if let Err(err) = write_atomically(INDEX_PATH, content).await {
+            warn!("{:?}", err.context("failed to persist mapset cache"));
+        }
+    }
+}
+
+impl Drop for MapsetGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.cache.state.lock().unwrap();
+
+        if let Some(count) = state.in_use.get_mut(&self.mapset_id) {
+            *count -= 1;
+
+            if *count == 0 {
+                state.in_use.remove(&self.mapset_id);
+            }
+        }
+    }
+}
+
+impl Drop for MapsetLock<'_> {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+
+        // Locks that no one holds or waits for anymore are created again when needed
+        self.cache
+            .locks
+            .lock()
+            .unwrap()
+            .retain(|_, lock| Arc::strong_count(lock) > 1);
+    }
+}
+
+pub fn mapset_dir(mapset_id: u32) -> PathBuf {
+    PathBuf::from(format!("{SONGS_DIR}/{mapset_id}"))
+}
+
+async fn dir_size(path: PathBuf) -> Result<u64> {
+    let mut size = 0;
+    let mut dirs = vec![path];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("failed to read dir `{}`", dir.display()))?;
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .with_context(|| format!("failed to get entry of `{}`", dir.display()))?
+        {
+            let metadata = entry.metadata().await.with_context(|| {
+                format!("failed to get metadata of `{}`", entry.path().display())
+            })?;
+
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+
+    Ok(size)
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
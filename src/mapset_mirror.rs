@@ -0,0 +1,423 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::Cursor,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use bytes::Bytes;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serenity::async_trait;
+use zip::ZipArchive;
+
+/// How long a mirror is skipped after it failed for the first time.
+/// Every further consecutive failure doubles the duration.
+const BASE_COOLDOWN: Duration = Duration::from_secs(60);
+const MAX_COOLDOWN: Duration = Duration::from_secs(30 * 60);
+
+/// A source for `.osz` archives.
+#[async_trait]
+pub trait MapsetMirror: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn download(&self, mapset_id: u32, client: &Client) -> Result<Bytes, MirrorError>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MirrorError {
+    /// The mirror couldn't be reached or failed on its end which counts against its health
+    #[error(transparent)]
+    Unavailable(Error),
+    /// The mirror works but can't serve this mapset, e.g. because it doesn't have it
+    #[error(transparent)]
+    Unserved(Error),
+}
+
+/// Configuration of a mirror as it is stored in `src/mirrors.json`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MirrorConfig {
+    pub name: String,
+    /// URL in which `{mapset_id}` is replaced with the id of the requested mapset
+    pub url: String,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// How often a failed download is repeated before moving on to the next mirror
+    #[serde(default)]
+    pub retries: u32,
+}
+
+fn default_timeout_secs() -> u64 {
+    120
+}
+
+impl MirrorConfig {
+    /// The mirrors that are used if `src/mirrors.json` did not exist yet.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self {
+                name: "kitsu".to_owned(),
+                url: "https://kitsu.moe/d/{mapset_id}".to_owned(),
+                timeout_secs: default_timeout_secs(),
+                retries: 1,
+            },
+            Self {
+                name: "chimu".to_owned(),
+                url: "https://chimu.moe/d/{mapset_id}".to_owned(),
+                timeout_secs: default_timeout_secs(),
+                retries: 1,
+            },
+        ]
+    }
+}
+
+/// A mirror that serves mapsets under a templated URL.
+pub struct UrlMirror {
+    config: MirrorConfig,
+}
+
+impl UrlMirror {
+    pub fn new(config: MirrorConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl MapsetMirror for UrlMirror {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    async fn download(&self, mapset_id: u32, client: &Client) -> Result<Bytes, MirrorError> {
+        let url = self
+            .config
+            .url
+            .replace("{mapset_id}", &mapset_id.to_string());
+
+        let timeout = Duration::from_secs(self.config.timeout_secs);
+        let mut attempt = 0;
+
+        loop {
+            let res = async {
+                let resp = match client.get(&url).timeout(timeout).send().await {
+                    Ok(resp) => resp,
+                    Err(err) => {
+                        let err = anyhow!("failed to GET using: {url}, error: {err}");
+
+                        return Err(MirrorError::Unavailable(err));
+                    }
+                };
+
+                let status = resp.status();
+
+                let resp = match resp.error_for_status() {
+                    Ok(resp) => resp,
+                    Err(err) if status.is_server_error() => {
+                        let err = anyhow!("bad response from {url}: {err}");
+
+                        return Err(MirrorError::Unavailable(err));
+                    }
+                    Err(err) => {
+                        let err = anyhow!("bad response from {url}: {err}");
+
+                        return Err(MirrorError::Unserved(err));
+                    }
+                };
+
+                let bytes = match resp.bytes().await {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        let err = anyhow!("failed to read bytes: {err}");
+
+                        return Err(MirrorError::Unavailable(err));
+                    }
+                };
+
+                // Mirrors like to respond with an error page instead of an error status
+                if let Err(err) = ZipArchive::new(Cursor::new(&bytes)) {
+                    let err = anyhow!("response is not a valid zip archive: {err}");
+
+                    return Err(MirrorError::Unserved(err));
+                }
+
+                Ok(bytes)
+            }
+            .await;
+
+            // Asking again won't make the mirror have the mapset
+            match res {
+                Ok(bytes) => return Ok(bytes),
+                Err(err @ MirrorError::Unserved(_)) => return Err(err),
+                Err(err) if attempt >= self.config.retries => return Err(err),
+                Err(err) => {
+                    attempt += 1;
+                    debug!("Retrying mirror {} ({attempt}): {err}", self.config.name);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct MirrorHealth {
+    consecutive_failures: u32,
+    skip_until: Option<Instant>,
+}
+
+struct TrackedMirror {
+    mirror: Box<dyn MapsetMirror>,
+    health: Mutex<MirrorHealth>,
+}
+
+/// Tries its mirrors in order, temporarily skipping the ones that failed recently.
+pub struct MirrorChain {
+    client: Client,
+    mirrors: Vec<TrackedMirror>,
+}
+
+impl MirrorChain {
+    pub fn new(client: Client, mirrors: Vec<Box<dyn MapsetMirror>>) -> Self {
+        let mirrors = mirrors
+            .into_iter()
+            .map(|mirror| TrackedMirror {
+                mirror,
+                health: Mutex::new(MirrorHealth::default()),
+            })
+            .collect();
+
+        Self { client, mirrors }
+    }
+
+    pub fn from_configs(client: Client, configs: Vec<MirrorConfig>) -> Self {
+        let mirrors = configs
+            .into_iter()
+            .map(|config| Box::new(UrlMirror::new(config)) as Box<dyn MapsetMirror>)
+            .collect();
+
+        Self::new(client, mirrors)
+    }
+
+    pub async fn download(&self, mapset_id: u32) -> Result<Bytes, MapsetDownloadError> {
+        let now = Instant::now();
+
+        let is_healthy = |tracked: &TrackedMirror| {
+            let health = tracked.health.lock().unwrap();
+
+            !matches!(health.skip_until, Some(until) if until > now)
+        };
+
+        // If every mirror is on cooldown, try all of them anyway
+        let any_healthy = self.mirrors.iter().any(is_healthy);
+        let mut attempts = Vec::with_capacity(self.mirrors.len());
+
+        for tracked in self.mirrors.iter() {
+            let name = tracked.mirror.name().to_owned();
+
+            if any_healthy && !is_healthy(tracked) {
+                attempts.push((name, MirrorAttempt::Skipped));
+                continue;
+            }
+
+            match tracked.mirror.download(mapset_id, &self.client).await {
+                Ok(bytes) => {
+                    *tracked.health.lock().unwrap() = MirrorHealth::default();
+
+                    return Ok(bytes);
+                }
+                Err(MirrorError::Unavailable(err)) => {
+                    let mut health = tracked.health.lock().unwrap();
+                    health.consecutive_failures += 1;
+
+                    let cooldown = BASE_COOLDOWN
+                        .saturating_mul(1 << (health.consecutive_failures - 1).min(5))
+                        .min(MAX_COOLDOWN);
+
+                    health.skip_until = Some(Instant::now() + cooldown);
+                    drop(health);
+
+                    warn!("Mirror {name} failed for mapset {mapset_id}: {err:#}");
+                    attempts.push((name, MirrorAttempt::Failed(err)));
+                }
+                // One missing mapset says nothing about the mirror's health
+                Err(MirrorError::Unserved(err)) => {
+                    warn!("Mirror {name} couldn't serve mapset {mapset_id}: {err:#}");
+                    attempts.push((name, MirrorAttempt::Failed(err)));
+                }
+            }
+        }
+
+        Err(MapsetDownloadError { attempts })
+    }
+}
+
+pub enum MirrorAttempt {
+    Failed(Error),
+    Skipped,
+}
+
+#[derive(thiserror::Error)]
+pub struct MapsetDownloadError {
+    pub attempts: Vec<(String, MirrorAttempt)>,
+}
+
+impl Display for MapsetDownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("failed to download mapset")?;
+
+        if self.attempts.is_empty() {
+            return f.write_str(", no mirrors are configured");
+        }
+
+        for (name, attempt) in self.attempts.iter() {
+            match attempt {
+                MirrorAttempt::Failed(err) => write!(f, "\n{name} error: {err}")?,
+                MirrorAttempt::Skipped => write!(f, "\n{name}: skipped after recent failures")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for MapsetDownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    #[derive(Copy, Clone)]
+    enum Outcome {
+        Success,
+        Unavailable,
+        Missing,
+    }
+
+    struct FakeState {
+        outcome: Mutex<Outcome>,
+        calls: AtomicUsize,
+    }
+
+    impl FakeState {
+        fn set(&self, outcome: Outcome) {
+            *self.outcome.lock().unwrap() = outcome;
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    struct FakeMirror {
+        name: &'static str,
+        state: Arc<FakeState>,
+    }
+
+    #[async_trait]
+    impl MapsetMirror for FakeMirror {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn download(&self, _: u32, _: &Client) -> Result<Bytes, MirrorError> {
+            self.state.calls.fetch_add(1, Ordering::SeqCst);
+
+            match *self.state.outcome.lock().unwrap() {
+                Outcome::Success => Ok(Bytes::from_static(self.name.as_bytes())),
+                Outcome::Unavailable => Err(MirrorError::Unavailable(anyhow!("timed out"))),
+                Outcome::Missing => Err(MirrorError::Unserved(anyhow!("404"))),
+            }
+        }
+    }
+
+    fn chain(outcomes: &[Outcome]) -> (MirrorChain, Vec<Arc<FakeState>>) {
+        const NAMES: [&str; 3] = ["first", "second", "third"];
+
+        let states: Vec<_> = outcomes
+            .iter()
+            .map(|&outcome| {
+                Arc::new(FakeState {
+                    outcome: Mutex::new(outcome),
+                    calls: AtomicUsize::new(0),
+                })
+            })
+            .collect();
+
+        let mirrors = states
+            .iter()
+            .zip(NAMES)
+            .map(|(state, name)| {
+                let state = Arc::clone(state);
+
+                Box::new(FakeMirror { name, state }) as Box<dyn MapsetMirror>
+            })
+            .collect();
+
+        (MirrorChain::new(Client::new(), mirrors), states)
+    }
+
+    #[tokio::test]
+    async fn falls_back_in_order() {
+        let (chain, states) = chain(&[Outcome::Unavailable, Outcome::Missing, Outcome::Success]);
+
+        assert_eq!(chain.download(1).await.unwrap(), "third");
+        assert_eq!(
+            states.iter().map(|s| s.calls()).collect::<Vec<_>>(),
+            [1, 1, 1]
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_unavailable_mirror_during_cooldown() {
+        let (chain, states) = chain(&[Outcome::Unavailable, Outcome::Success]);
+
+        assert_eq!(chain.download(1).await.unwrap(), "second");
+
+        states[0].set(Outcome::Success);
+        assert_eq!(chain.download(2).await.unwrap(), "second");
+        assert_eq!(states[0].calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_mapset_keeps_mirror_healthy() {
+        let (chain, states) = chain(&[Outcome::Missing, Outcome::Success]);
+
+        assert_eq!(chain.download(1).await.unwrap(), "second");
+
+        states[0].set(Outcome::Success);
+        assert_eq!(chain.download(2).await.unwrap(), "first");
+        assert_eq!(states[0].calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn tries_all_mirrors_if_all_are_on_cooldown() {
+        let (chain, states) = chain(&[Outcome::Unavailable, Outcome::Unavailable]);
+
+        assert!(chain.download(1).await.is_err());
+
+        states[1].set(Outcome::Success);
+        assert_eq!(chain.download(2).await.unwrap(), "second");
+        assert_eq!(states[0].calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn reports_skipped_mirrors() {
+        let (chain, _) = chain(&[Outcome::Unavailable, Outcome::Missing]);
+
+        assert!(chain.download(1).await.is_err());
+
+        let err = chain.download(2).await.unwrap_err();
+
+        assert!(matches!(err.attempts[0].1, MirrorAttempt::Skipped));
+        assert!(matches!(err.attempts[1].1, MirrorAttempt::Failed(_)));
+    }
+}
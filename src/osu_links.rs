@@ -0,0 +1,52 @@
+use std::{collections::HashMap, io::ErrorKind};
+
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+use tokio::fs;
+
+const LINKS_PATH: &str = "src/osu_links.json";
+
+/// The osu! accounts of discord users so they don't have to name themselves in every command.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct OsuLinks {
+    links: HashMap<UserId, u32>,
+}
+
+impl OsuLinks {
+    pub async fn load() -> Result<Self> {
+        match fs::read_to_string(LINKS_PATH).await {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("failed to deserialize `{LINKS_PATH}`")),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(Error::new(err).context(format!("failed to read `{LINKS_PATH}`"))),
+        }
+    }
+
+    /// The osu! user id that the discord user linked.
+    pub fn get(&self, user: UserId) -> Option<u32> {
+        self.links.get(&user).copied()
+    }
+
+    pub fn insert(&mut self, user: UserId, osu_user: u32) {
+        self.links.insert(user, osu_user);
+    }
+
+    /// Write the links to disk so they survive restarts.
+    pub async fn persist(&self) {
+        let content = match serde_json::to_string(self) {
+            Ok(content) => content,
+            Err(why) => {
+                let err = Error::new(why).context("failed to serialize osu! links");
+                warn!("{err:?}");
+
+                return;
+            }
+        };
+
+        if let Err(why) = fs::write(LINKS_PATH, content).await {
+            let err = Error::new(why).context(format!("failed writing to `{LINKS_PATH}`"));
+            warn!("{err:?}");
+        }
+    }
+}
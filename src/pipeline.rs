@@ -0,0 +1,915 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    future::Future,
+    io::{Cursor, Error as IoError, Result as IoResult},
+    iter,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Error, Result};
+use rosu_pp::{Beatmap, BeatmapExt};
+use rosu_v2::{
+    error::OsuError,
+    prelude::{Beatmap as Map, GameMods, Osu},
+};
+use serenity::http::Http;
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::Command,
+    time,
+};
+use zip::ZipArchive;
+
+use crate::{
+    announcement::{Announcement, RenderInfo},
+    mapset_cache::{mapset_dir, MapsetCache},
+    mapset_mirror::MirrorChain,
+    play_stats::PlayStats,
+    process_replays::{path_exists, replay_length, Data},
+    render_cache::{RenderCache, RenderKey},
+    replay_analysis::map_end,
+    replay_queue::{Cancellation, ReplayStatus},
+    server_settings::VideoDelivery,
+    status_message::{parse_progress, StatusMessage},
+    title_template::{TitleTemplate, TitleValues},
+    upload_backend::{DiscordAttachment, UploadBackend, UploadError, UploadRequest, UploadedVideo},
+    util::{OsuFile, UploadProgress},
+    ReplayQueue,
+};
+
+/// How often a stage is attempted if it keeps failing with a retryable error.
+const STAGE_ATTEMPTS: u32 = 3;
+/// Waiting time before the first retry, every further retry waits longer.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Everything the render workers share.
+pub struct WorkerContext {
+    pub osu: Arc<Osu>,
+    pub http: Arc<Http>,
+    pub mirrors: Arc<MirrorChain>,
+    pub queue: Arc<ReplayQueue>,
+    pub mapsets: Arc<MapsetCache>,
+    pub renders: Arc<RenderCache>,
+    pub uploader: Box<dyn UploadBackend>,
+    /// Used instead of `uploader` for videos that fit within discord's upload limit
+    pub attachments: DiscordAttachment,
+    pub render_timeout: Duration,
+    /// Videos larger than this many bytes are not uploaded
+    pub max_upload_size: Option<u64>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Stage {
+    ResolveMap,
+    Download,
+    Render,
+    Stats,
+    LocateOutput,
+    Upload,
+    Announce,
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let stage = match self {
+            Self::ResolveMap => "resolve map",
+            Self::Download => "download",
+            Self::Render => "render",
+            Self::Stats => "stats",
+            Self::LocateOutput => "locate output",
+            Self::Upload => "upload",
+            Self::Announce => "announce",
+        };
+
+        f.write_str(stage)
+    }
+}
+
+/// Why a replay could not make it through the pipeline.
+#[derive(Debug, thiserror::Error)]
+#[error("{stage} stage failed: {user_message}")]
+pub struct PipelineError {
+    pub stage: Stage,
+    /// Sent to the user who queued the replay
+    pub user_message: String,
+    /// Logged including its whole context chain
+    pub log: Error,
+    /// Whether attempting the stage again might succeed
+    pub retryable: bool,
+}
+
+impl PipelineError {
+    pub fn new(stage: Stage, user_message: impl Into<String>, log: impl Into<Error>) -> Self {
+        Self {
+            stage,
+            user_message: user_message.into(),
+            log: log.into(),
+            retryable: false,
+        }
+    }
+
+    pub fn retryable(mut self) -> Self {
+        self.retryable = true;
+
+        self
+    }
+}
+
+type PipelineResult<T> = Result<T, PipelineError>;
+
+enum Outcome {
+    Done,
+    Cancelled,
+}
+
+struct ResolvedMap {
+    hash: String,
+    mapset_id: u32,
+    /// Drain time of the map in seconds
+    seconds_drain: u32,
+}
+
+/// A replay that is being worked on by a worker.
+struct Job<'c> {
+    worker: usize,
+    ctx: &'c WorkerContext,
+    data: Data,
+    status: StatusMessage,
+    cancellation: Cancellation,
+}
+
+pub async fn process_replay(worker: usize, ctx: Arc<WorkerContext>) {
+    loop {
+        let (data, cancellation) = ctx.queue.take(worker).await;
+        let status = StatusMessage::new(Arc::clone(&ctx.http), &data).await;
+
+        let mut job = Job {
+            worker,
+            ctx: &ctx,
+            data,
+            status,
+            cancellation,
+        };
+
+        match job.run().await {
+            Ok(Outcome::Done) => job.status.done().await,
+            Ok(Outcome::Cancelled) => {
+                info!("Worker {worker}: Replay was cancelled");
+                job.status.cancelled().await;
+            }
+            Err(err) => {
+                let log = err
+                    .log
+                    .context(format!("Worker {worker}: {} stage failed", err.stage));
+                warn!("{log:?}");
+
+                job.status.fail(err.user_message).await;
+            }
+        }
+
+        ctx.queue.finish(worker).await;
+    }
+}
+
+impl Job<'_> {
+    async fn run(&mut self) -> PipelineResult<Outcome> {
+        let ctx = self.ctx;
+
+        let render_key = match RenderKey::new(&self.data).await {
+            Ok(key) => Some(key),
+            Err(why) => {
+                warn!("{:?}", why.context("failed to create render key"));
+
+                None
+            }
+        };
+
+        // An identical replay might have been rendered while this one was waiting
+        if let Some(link) = render_key.as_ref().and_then(|key| ctx.renders.get(key)) {
+            info!("Worker {}: Reusing previous render", self.worker);
+            let video = UploadedVideo::Link(link);
+            retry(self.worker, || self.announce(&video, None)).await?;
+
+            return Ok(Outcome::Done);
+        }
+
+        let map = retry(self.worker, || self.resolve_map()).await?;
+        let _mapset_guard = ctx.mapsets.acquire(map.mapset_id).await;
+        let osu_file = self.download(&map).await?;
+        let beatmap = self.parse_beatmap(&osu_file).await?;
+
+        // Players of a knockout may use different mods so there is no single play to rate
+        let stats = self
+            .data
+            .knockout
+            .is_empty()
+            .then(|| PlayStats::new(&self.data.replay, &beatmap));
+
+        let trim = self.trim(&beatmap)?;
+
+        if self.cancellation.is_cancelled() {
+            return Ok(Outcome::Cancelled);
+        }
+
+        // Workers render concurrently so each one needs its own output and log file
+        let out_name = format!("{}_{}", self.file_stem()?, self.worker);
+        let settings = self.settings_name().await;
+        let render_start = Instant::now();
+        self.render(&out_name, &settings, trim).await?;
+
+        let render = RenderInfo {
+            duration: render_start.elapsed(),
+            settings,
+        };
+
+        // Cancelled renders stop early and would make the estimate too optimistic
+        if self.cancellation.is_cancelled() {
+            return Ok(Outcome::Cancelled);
+        }
+
+        let length = replay_length(map.seconds_drain, &self.data.replay, trim);
+        ctx.queue.record_render(length, render.duration).await;
+
+        let video_path = self.locate_output(&out_name).await?;
+        let title = create_title(&self.data, &osu_file, &beatmap, stats.as_ref());
+
+        let announcement = Announcement::new(
+            &self.data,
+            &title,
+            &osu_file,
+            &beatmap,
+            stats.as_ref(),
+            &render,
+        )
+        .await;
+
+        if self.cancellation.is_cancelled() {
+            return Ok(Outcome::Cancelled);
+        }
+
+        let video = self.upload(&title, &video_path, &announcement).await?;
+
+        // Posted videos have already been announced along with the upload
+        if matches!(video, UploadedVideo::Link(_)) && self.cancellation.is_cancelled() {
+            return Ok(Outcome::Cancelled);
+        }
+
+        retry(self.worker, || self.announce(&video, Some(&announcement))).await?;
+
+        // Posted videos can only be opened by members of the output channel
+        if let (Some(key), UploadedVideo::Link(link)) = (render_key, video) {
+            ctx.renders.insert(key, link).await;
+        }
+
+        Ok(Outcome::Done)
+    }
+
+    async fn set_status(&mut self, status: ReplayStatus) {
+        self.ctx.queue.set_status(self.worker, status).await;
+        self.status.set_status(status).await;
+    }
+
+    async fn resolve_map(&self) -> PipelineResult<ResolvedMap> {
+        let stage = Stage::ResolveMap;
+
+        let hash = match self.data.replay.beatmap_hash.as_deref() {
+            Some(hash) => hash,
+            None => {
+                let log = anyhow!("no hash in replay requested by user {}", self.data.user);
+
+                return Err(PipelineError::new(
+                    stage,
+                    "couldn't find hash in your replay file",
+                    log,
+                ));
+            }
+        };
+
+        let (mapset, seconds_drain) = match self.ctx.osu.beatmap().checksum(hash).await {
+            Ok(Map {
+                mapset,
+                seconds_drain,
+                ..
+            }) => (mapset, seconds_drain),
+            Err(OsuError::NotFound) => {
+                let log = anyhow!("no map with hash `{hash}`");
+                let content = "the map of your replay is not submitted or has been updated";
+
+                return Err(PipelineError::new(stage, content, log));
+            }
+            Err(why) => {
+                let log =
+                    Error::new(why).context(format!("failed to request map with hash `{hash}`"));
+                let content = format!("failed to get the map with hash: `{hash}`");
+
+                return Err(PipelineError::new(stage, content, log).retryable());
+            }
+        };
+
+        match mapset {
+            Some(mapset) => Ok(ResolvedMap {
+                hash: hash.to_owned(),
+                mapset_id: mapset.mapset_id,
+                seconds_drain,
+            }),
+            None => {
+                let log = anyhow!("missing mapset in map with hash `{hash}`");
+
+                Err(PipelineError::new(
+                    stage,
+                    "the mapset is missing in the map",
+                    log,
+                ))
+            }
+        }
+    }
+
+    /// Find the played `.osu` file, downloading its mapset if it's not cached.
+    async fn download(&mut self, map: &ResolvedMap) -> PipelineResult<OsuFile> {
+        let stage = Stage::Download;
+        let ctx = self.ctx;
+        let ResolvedMap {
+            hash, mapset_id, ..
+        } = map;
+        let mapset_id = *mapset_id;
+        let _download_guard = ctx.mapsets.lock(mapset_id).await;
+
+        if ctx.mapsets.contains(mapset_id) {
+            match OsuFile::find_by_hash(mapset_dir(mapset_id), hash).await {
+                Ok(Some(osu_file)) => {
+                    info!("Worker {}: Using cached mapset {mapset_id}", self.worker);
+
+                    return Ok(osu_file);
+                }
+                Ok(None) => {}
+                Err(why) => warn!("{:?}", why.context("failed to check cached mapset")),
+            }
+        }
+
+        info!("Worker {}: Started map download", self.worker);
+        self.set_status(ReplayStatus::Downloading).await;
+
+        // The mirrors retry on their own so the stage is not repeated
+        if let Err(err) = download_mapset(mapset_id, &ctx.mirrors).await {
+            let content = format!("failed to download map: {err}");
+
+            return Err(PipelineError::new(stage, content, err));
+        }
+
+        if let Err(why) = ctx.mapsets.insert(mapset_id).await {
+            warn!("{:?}", why.context("failed to add mapset to the cache"));
+        }
+
+        info!("Worker {}: Finished map download", self.worker);
+
+        match OsuFile::find_by_hash(mapset_dir(mapset_id), hash).await {
+            Ok(Some(osu_file)) => Ok(osu_file),
+            Ok(None) => {
+                let log = anyhow!("no .osu file of mapset {mapset_id} matches the hash `{hash}`");
+                let content = "couldn't find the played difficulty in the downloaded mapset";
+
+                Err(PipelineError::new(stage, content, log))
+            }
+            Err(why) => {
+                let log = why.context("failed to resolve .osu file");
+                let content = "there was an error resolving the beatmap file";
+
+                Err(PipelineError::new(stage, content, log))
+            }
+        }
+    }
+
+    /// The replay's file name without directory and extension.
+    fn file_stem(&self) -> PipelineResult<&str> {
+        let path = &self.data.path;
+
+        match path
+            .split('/')
+            .next_back()
+            .and_then(|file| file.split('.').next())
+        {
+            Some(name) => Ok(name),
+            None => {
+                let log = anyhow!("replay path `{path}` has an unexpected form");
+                let content = "there was an error resolving the replay path";
+
+                Err(PipelineError::new(Stage::Render, content, log))
+            }
+        }
+    }
+
+    /// Name of the danser settings file to render with, the user's own if there is one.
+    async fn settings_name(&self) -> String {
+        let user = self.data.user;
+
+        if path_exists(format!("../danser/settings/{user}.json")).await {
+            user.to_string()
+        } else {
+            "default".to_string()
+        }
+    }
+
+    /// Start and end in milliseconds of the part of the map that is rendered.
+    fn trim(&self, beatmap: &Beatmap) -> PipelineResult<Option<(u32, u32)>> {
+        let time_points = match self.data.time_points {
+            Some(time_points) => time_points,
+            None => return Ok(None),
+        };
+
+        // The map might have been unavailable when the time points were first checked
+        match time_points.resolve(map_end(beatmap)) {
+            Ok(trim) => Ok(Some(trim)),
+            Err(rejection) => {
+                let log = anyhow!("invalid time points {time_points:?}: {rejection}");
+
+                Err(PipelineError::new(
+                    Stage::Render,
+                    rejection.to_string(),
+                    log,
+                ))
+            }
+        }
+    }
+
+    /// Run danser until it's done, timed out, or the replay got cancelled.
+    async fn render(
+        &mut self,
+        out_name: &str,
+        settings: &str,
+        trim: Option<(u32, u32)>,
+    ) -> PipelineResult<()> {
+        let stage = Stage::Render;
+
+        let mut command = Command::new("../danser/danser");
+
+        command.kill_on_drop(true);
+
+        if self.data.knockout.is_empty() {
+            command.arg(format!("-replay={}", self.data.path));
+        } else {
+            let replays = iter::once(&self.data.path)
+                .chain(self.data.knockout.iter())
+                .collect::<Vec<_>>();
+
+            // danser takes the replays of a knockout as json array
+            let replays = serde_json::to_string(&replays).map_err(|why| {
+                let log = Error::new(why).context("failed to serialize knockout replays");
+
+                PipelineError::new(stage, "failed to prepare knockout", log)
+            })?;
+
+            command.arg(format!("-knockout2={replays}"));
+        }
+
+        command
+            .arg("-record")
+            .arg(format!("-settings={}", settings))
+            .arg("-quickstart")
+            .arg(format!("-out={}", out_name));
+
+        // danser takes the times in seconds
+        if let Some((start, end)) = trim {
+            command.args(["-start", &format!("{:.3}", start as f64 / 1000.0)]);
+            command.args(["-end", &format!("{:.3}", end as f64 / 1000.0)]);
+        }
+
+        info!("Worker {}: Started replay parsing", self.worker);
+        self.set_status(ReplayStatus::Processing).await;
+
+        let render_timeout = self.ctx.render_timeout;
+
+        let output = tokio::select! {
+            output = time::timeout(render_timeout, run_danser(command, &mut self.status)) => output,
+            _ = self.cancellation.cancelled() => return Ok(()),
+        };
+
+        let output = match output {
+            Ok(Ok(output)) => output,
+            Ok(Err(why)) => {
+                let log = Error::new(why).context("failed to get command output");
+                let content = format!("failed to parse replay: {log}");
+
+                return Err(PipelineError::new(stage, content, log));
+            }
+            Err(_) => {
+                let log = anyhow!(
+                    "rendering `{}` timed out after {}s",
+                    self.data.path,
+                    render_timeout.as_secs()
+                );
+
+                let content = format!(
+                    "rendering took longer than {} minutes and was aborted",
+                    render_timeout.as_secs() / 60
+                );
+
+                return Err(PipelineError::new(stage, content, log));
+            }
+        };
+
+        debug!("stdout: {}", output.stdout);
+        debug!("stderr: {}", output.stderr);
+
+        let log_path = format!("../Replays/{out_name}.log");
+
+        if let Err(why) = fs::write(&log_path, &output.stdout).await {
+            let err = Error::new(why).context(format!("failed to write `{log_path}`"));
+            warn!("{:?}", err);
+        }
+
+        info!("Worker {}: Finished replay parsing", self.worker);
+
+        Ok(())
+    }
+
+    async fn locate_output(&self, out_name: &str) -> PipelineResult<PathBuf> {
+        let path = PathBuf::from(format!("../Replays/{out_name}.mp4"));
+
+        if path_exists(&path).await {
+            Ok(path)
+        } else {
+            let log = anyhow!(
+                "danser did not create `{}`, check `../Replays/{out_name}.log`",
+                path.display()
+            );
+
+            Err(PipelineError::new(
+                Stage::LocateOutput,
+                "danser failed to create a video of your replay",
+                log,
+            ))
+        }
+    }
+
+    async fn parse_beatmap(&self, osu_file: &OsuFile) -> PipelineResult<Beatmap> {
+        Beatmap::from_path(&osu_file.path).await.map_err(|why| {
+            let log = Error::new(why).context(format!(
+                "failed to parse beatmap `{}`",
+                osu_file.path.display()
+            ));
+            let content = "there was an error while trying to read the map of your replay";
+
+            PipelineError::new(Stage::Stats, content, log)
+        })
+    }
+
+    /// Attach the video if it's small enough, otherwise upload it.
+    async fn upload(
+        &mut self,
+        title: &str,
+        video_path: &Path,
+        announcement: &Announcement,
+    ) -> PipelineResult<UploadedVideo> {
+        let stage = Stage::Upload;
+        let ctx = self.ctx;
+
+        let size = match fs::metadata(video_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(why) => {
+                let log = Error::new(why).context(format!(
+                    "failed to get metadata of `{}`",
+                    video_path.display()
+                ));
+
+                return Err(PipelineError::new(
+                    stage,
+                    "failed to read the video file",
+                    log,
+                ));
+            }
+        };
+
+        let fits_attachment = size <= ctx.attachments.limit(self.data.guild).await;
+
+        if fits_attachment && self.data.delivery == VideoDelivery::Attachment {
+            return self
+                .upload_via(&ctx.attachments, title, video_path, announcement, size)
+                .await;
+        }
+
+        match ctx.max_upload_size {
+            Some(max_size) if size > max_size && fits_attachment => {
+                return self
+                    .upload_via(&ctx.attachments, title, video_path, announcement, size)
+                    .await;
+            }
+            Some(max_size) if size > max_size => {
+                let log = anyhow!("`{}` has {size} bytes", video_path.display());
+
+                let content = format!(
+                    "the video is {:.1} MB which exceeds the maximum upload size of {} MB",
+                    size as f64 / (1024.0 * 1024.0),
+                    max_size / (1024 * 1024)
+                );
+
+                return Err(PipelineError::new(stage, content, log));
+            }
+            _ => {}
+        }
+
+        match self
+            .upload_via(&*ctx.uploader, title, video_path, announcement, size)
+            .await
+        {
+            Ok(video) => Ok(video),
+            Err(err) if fits_attachment => {
+                let log = err.log.context("sending the video as attachment instead");
+                warn!("Worker {}: {log:?}", self.worker);
+
+                self.upload_via(&ctx.attachments, title, video_path, announcement, size)
+                    .await
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Upload the video while showing how much of it has been sent.
+    async fn upload_via(
+        &mut self,
+        backend: &dyn UploadBackend,
+        title: &str,
+        video_path: &Path,
+        announcement: &Announcement,
+        size: u64,
+    ) -> PipelineResult<UploadedVideo> {
+        let stage = Stage::Upload;
+        let worker = self.worker;
+
+        self.set_status(ReplayStatus::Uploading).await;
+        info!("Worker {worker}: Started upload via {}", backend.name());
+
+        let data = &self.data;
+        let progress = UploadProgress::default();
+
+        let upload_fut = retry(worker, || async {
+            progress.reset();
+
+            let request = UploadRequest {
+                title,
+                path: video_path,
+                user: data.user,
+                guild: data.guild,
+                channel: data.output_channel,
+                announcement,
+                progress: progress.clone(),
+            };
+
+            match backend.upload(request).await {
+                Ok(video) => Ok(video),
+                Err(UploadError::Rejected(reason)) => {
+                    let log = anyhow!("{} rejected the upload: {reason}", backend.name());
+                    let content = format!("failed to upload: `{reason}`");
+
+                    Err(PipelineError::new(stage, content, log))
+                }
+                Err(UploadError::Other(why)) => {
+                    let log = why.context(format!("failed to upload via {}", backend.name()));
+                    let content = format!("failed to upload to {}", backend.name());
+
+                    Err(PipelineError::new(stage, content, log).retryable())
+                }
+            }
+        });
+
+        tokio::pin!(upload_fut);
+        let mut interval = time::interval(Duration::from_secs(1));
+
+        let video = loop {
+            tokio::select! {
+                res = &mut upload_fut => break res?,
+                _ = interval.tick() => {
+                    let sent = progress.sent().min(size);
+
+                    if sent > 0 {
+                        self.status.set_progress((sent * 100 / size) as u8).await;
+                    }
+                }
+            }
+        };
+
+        info!("Worker {worker}: Finished upload via {}", backend.name());
+
+        Ok(video)
+    }
+
+    /// Send the link to the video, as plain message if there is no `announcement`.
+    ///
+    /// Not every job comes with [`PlayStats`]: reused renders are announced without the map
+    /// being downloaded so they only get the plain message, and knockouts have no single play
+    /// to rate so their embed only shows the map's stars.
+    async fn announce(
+        &self,
+        video: &UploadedVideo,
+        announcement: Option<&Announcement>,
+    ) -> PipelineResult<()> {
+        let link = match video {
+            UploadedVideo::Link(link) => link,
+            UploadedVideo::Posted => return Ok(()),
+        };
+
+        // The thumbnail is attached to the announcement so it must fit the limit on its own
+        let without_background;
+
+        let announcement = match announcement {
+            Some(announcement) if announcement.background_size() > 0 => {
+                let limit = self.ctx.attachments.limit(self.data.guild).await;
+
+                if announcement.background_size() > limit {
+                    without_background = announcement.without_background();
+
+                    Some(&without_background)
+                } else {
+                    Some(announcement)
+                }
+            }
+            announcement => announcement,
+        };
+
+        let msg_fut =
+            self.data
+                .output_channel
+                .send_message(&self.ctx.http, |m| match announcement {
+                    Some(announcement) => announcement.create(m, Some(link)),
+                    None => m.content(self.data.announcement(link)),
+                });
+
+        match msg_fut.await {
+            Ok(_) => Ok(()),
+            Err(why) => {
+                let log = Error::new(why).context("failed to send video link");
+                let content = format!("your replay is ready but I couldn't post it: {link}");
+
+                Err(PipelineError::new(Stage::Announce, content, log).retryable())
+            }
+        }
+    }
+}
+
+/// Run a stage until it succeeds, fails with a non-retryable error,
+/// or ran out of attempts.
+async fn retry<T, F, Fut>(worker: usize, mut stage: F) -> PipelineResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = PipelineResult<T>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match stage().await {
+            Err(err) if err.retryable && attempt < STAGE_ATTEMPTS => {
+                warn!(
+                    "Worker {worker}: {} stage failed, retrying ({attempt}/{}): {:#}",
+                    err.stage,
+                    STAGE_ATTEMPTS - 1,
+                    err.log
+                );
+
+                time::sleep(RETRY_DELAY * attempt).await;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+struct DanserOutput {
+    stdout: String,
+    stderr: String,
+}
+
+/// Run danser to completion while forwarding its render progress to the status message.
+async fn run_danser(mut command: Command, status: &mut StatusMessage) -> IoResult<DanserOutput> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let child_stdout = child.stdout.take().expect("stdout is piped");
+    let mut child_stderr = child.stderr.take().expect("stderr is piped");
+
+    let stdout_fut = async {
+        let mut lines = BufReader::new(child_stdout).split(b'\n');
+        let mut stdout = String::new();
+
+        while let Some(line) = lines.next_segment().await? {
+            let line = String::from_utf8_lossy(&line);
+
+            if let Some(progress) = parse_progress(&line) {
+                status.set_progress(progress).await;
+            }
+
+            stdout.push_str(&line);
+            stdout.push('\n');
+        }
+
+        Ok::<_, IoError>(stdout)
+    };
+
+    let stderr_fut = async {
+        let mut stderr = Vec::new();
+        child_stderr.read_to_end(&mut stderr).await?;
+
+        Ok::<_, IoError>(String::from_utf8_lossy(&stderr).into_owned())
+    };
+
+    let (stdout, stderr) = tokio::try_join!(stdout_fut, stderr_fut)?;
+    child.wait().await?;
+
+    Ok(DanserOutput { stdout, stderr })
+}
+
+async fn download_mapset(mapset_id: u32, mirrors: &MirrorChain) -> Result<()> {
+    let out_path = mapset_dir(mapset_id);
+    let bytes = mirrors.download(mapset_id).await?;
+    let cursor = Cursor::new(bytes);
+
+    let mut archive = match ZipArchive::new(cursor) {
+        Ok(archive) => archive,
+        Err(err) => return Err(anyhow!("failed to create zip archive: {err}")),
+    };
+
+    // Remove outdated files of a previous download
+    if path_exists(&out_path).await {
+        if let Err(err) = fs::remove_dir_all(&out_path).await {
+            return Err(anyhow!(
+                "failed to remove outdated mapset at `{}`, error: {err}",
+                out_path.display()
+            ));
+        }
+    }
+
+    match archive.extract(&out_path) {
+        Ok(()) => (),
+        Err(err) => {
+            return Err(anyhow!(
+                "failed to extract zip archive at `{}`, error: {err}",
+                out_path.display()
+            ))
+        }
+    };
+
+    Ok(())
+}
+
+/// The title of the video, `stats` being `None` for knockouts.
+fn create_title(
+    data: &Data,
+    osu_file: &OsuFile,
+    beatmap: &Beatmap,
+    stats: Option<&PlayStats>,
+) -> String {
+    let metadata = &osu_file.metadata;
+
+    let stats = match stats {
+        Some(stats) => stats.clone(),
+        None => {
+            let stars = beatmap.stars(0, None).stars();
+            let stars = (stars * 100.0).round() / 100.0;
+            let players = data.knockout.len() + 1;
+
+            return format!(
+                "[{stars}⭐] Knockout of {players} players | {} - {} [{}]",
+                metadata.artist, metadata.title, metadata.version
+            );
+        }
+    };
+
+    let mods_str = GameMods::from_bits(data.replay.mods.bits())
+        .unwrap_or_default()
+        .to_string();
+
+    let values = TitleValues {
+        player: data.replay.player_name.clone().unwrap_or_default(),
+        artist: metadata.artist.clone(),
+        title: metadata.title.clone(),
+        difficulty: metadata.version.clone(),
+        mods: if mods_str == "NM" {
+            String::new()
+        } else {
+            format!("+{mods_str}")
+        },
+        mapper: metadata.creator.clone(),
+        stats,
+    };
+
+    let template = match data
+        .title_template
+        .as_deref()
+        .map(str::parse::<TitleTemplate>)
+    {
+        Some(Ok(template)) => template,
+        Some(Err(why)) => {
+            let err = Error::new(why).context("invalid title template, using the default");
+            warn!("{err:?}");
+
+            TitleTemplate::default()
+        }
+        None => TitleTemplate::default(),
+    };
+
+    template.render(&values)
+}
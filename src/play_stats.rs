@@ -0,0 +1,153 @@
+use osu_db::Replay;
+use rosu_pp::{Beatmap, OsuPP};
+use rosu_v2::prelude::{GameMode, GameMods};
+
+/// Performance of a replay on its map.
+#[derive(Clone, Debug)]
+pub struct PlayStats {
+    /// Star rating of the whole map with the replay's mods
+    pub stars: f64,
+    pub pp: f64,
+    /// The pp if the misses were 300s and the combo was the map's max combo
+    pub fc_pp: f64,
+    pub combo: u32,
+    pub max_combo: u32,
+    pub misses: u32,
+    pub acc: f32,
+    pub grade: &'static str,
+}
+
+impl PlayStats {
+    /// Calculate the stats of an osu!standard replay.
+    pub fn new(replay: &Replay, map: &Beatmap) -> Self {
+        let mods = replay.mods.bits();
+        let n_objects = map.hit_objects.len();
+        let passed_objects = total_hits(replay, GameMode::STD) as usize;
+
+        // Leaving out the 300s lets rosu-pp fill them up to the whole map
+        let fc = OsuPP::new(map)
+            .mods(mods)
+            .n100(replay.count_100 as usize)
+            .n50(replay.count_50 as usize)
+            .misses(0)
+            .calculate();
+
+        let play = OsuPP::new(map)
+            .mods(mods)
+            .combo(replay.max_combo as usize)
+            .n300(replay.count_300 as usize)
+            .n100(replay.count_100 as usize)
+            .n50(replay.count_50 as usize)
+            .misses(replay.count_miss as usize);
+
+        // A failed play is only rated on the objects up to the point of failing
+        let play = if passed_objects < n_objects {
+            play.passed_objects(passed_objects).calculate()
+        } else {
+            play.attributes(fc.difficulty.clone()).calculate()
+        };
+
+        let grade = if passed_objects < n_objects {
+            "F"
+        } else {
+            grade(replay)
+        };
+
+        Self {
+            stars: fc.difficulty.stars,
+            pp: play.pp,
+            fc_pp: fc.pp,
+            combo: replay.max_combo as u32,
+            max_combo: fc.difficulty.max_combo as u32,
+            misses: replay.count_miss as u32,
+            acc: accuracy(replay, GameMode::STD),
+            grade,
+        }
+    }
+
+    pub fn is_full_combo(&self) -> bool {
+        self.misses == 0 && self.combo >= self.max_combo
+    }
+}
+
+/// The osu!standard grade of a passed replay, `SSH` and `SH` being the silver grades.
+fn grade(replay: &Replay) -> &'static str {
+    let total = total_hits(replay, GameMode::STD) as f32;
+
+    if total == 0.0 {
+        return "D";
+    }
+
+    let ratio_300 = replay.count_300 as f32 / total;
+    let ratio_50 = replay.count_50 as f32 / total;
+    let no_misses = replay.count_miss == 0;
+
+    let silver = GameMods::from_bits(replay.mods.bits())
+        .unwrap_or_default()
+        .intersects(GameMods::Hidden | GameMods::Flashlight);
+
+    if replay.count_300 as f32 == total {
+        if silver {
+            "SSH"
+        } else {
+            "SS"
+        }
+    } else if ratio_300 > 0.9 && ratio_50 <= 0.01 && no_misses {
+        if silver {
+            "SH"
+        } else {
+            "S"
+        }
+    } else if (ratio_300 > 0.8 && no_misses) || ratio_300 > 0.9 {
+        "A"
+    } else if (ratio_300 > 0.7 && no_misses) || ratio_300 > 0.8 {
+        "B"
+    } else if ratio_300 > 0.6 {
+        "C"
+    } else {
+        "D"
+    }
+}
+
+/// Accuracy of the replay in percent.
+pub fn accuracy(replay: &Replay, mode: GameMode) -> f32 {
+    let amount_objects = total_hits(replay, mode) as f32;
+
+    let (numerator, denumerator) = match mode {
+        GameMode::TKO => (
+            0.5 * replay.count_100 as f32 + replay.count_300 as f32,
+            amount_objects,
+        ),
+        GameMode::CTB => (
+            (replay.count_300 + replay.count_100 + replay.count_50) as f32,
+            amount_objects,
+        ),
+        GameMode::STD | GameMode::MNA => {
+            let mut n = (replay.count_50 as u32 * 50
+                + replay.count_100 as u32 * 100
+                + replay.count_300 as u32 * 300) as f32;
+
+            n += ((mode == GameMode::MNA) as u32
+                * (replay.count_katsu as u32 + replay.count_geki as u32)) as f32;
+
+            (n, amount_objects * 300.0)
+        }
+    };
+
+    (10_000.0 * numerator / denumerator).round() / 100.0
+}
+
+fn total_hits(replay: &Replay, mode: GameMode) -> u32 {
+    let mut amount = (replay.count_300 + replay.count_100 + replay.count_miss) as u32;
+
+    if mode != GameMode::TKO {
+        amount += replay.count_50 as u32;
+
+        if mode != GameMode::STD {
+            amount += replay.count_katsu as u32;
+            amount += (mode != GameMode::CTB) as u32 * replay.count_geki as u32;
+        }
+    }
+
+    amount
+}
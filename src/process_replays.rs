@@ -1,34 +1,56 @@
-use std::{env, fmt::Display, io::Cursor, sync::Arc};
+use std::{
+    fmt::Display,
+    io::{Cursor, Read},
+    path::Path,
+    sync::Arc,
+};
 
-use anyhow::{Context, Error, Result};
+use anyhow::{Context as AnyhowContext, Error, Result};
 use osu_db::Replay;
-use reqwest::Client;
-use rosu_pp::{Beatmap, BeatmapExt};
-use rosu_v2::prelude::{Beatmap as Map, Beatmapset, GameMode, GameMods, Osu};
+use rosu_pp::Beatmap;
+use rosu_v2::{
+    error::OsuError,
+    prelude::{Beatmap as Map, GameMode, GameMods},
+};
+use serde::{Deserialize, Serialize};
 use serenity::{
     http::Http,
     model::{
-        channel::Message,
-        id::{ChannelId, UserId},
+        channel::{Attachment, Message, ReactionType},
+        id::{ChannelId, GuildId, MessageId, UserId},
     },
-    prelude::{RwLock, TypeMap},
+    prelude::Context,
+    Result as SerenityResult,
 };
 use tokio::{
-    fs::{self, DirEntry, File},
+    fs::{self, File},
     io::AsyncWriteExt,
-    process::Command,
+    task,
 };
-use zip::ZipArchive;
+use zip::{result::ZipError, ZipArchive};
 
 use crate::{
-    replay_queue::ReplayStatus,
-    util::{levenshtein_similarity, CustomUploadApi},
-    ReplayHandler, ReplayQueue, ServerSettings,
+    render_cache::RenderKey,
+    replay_analysis::{map_end, Analysis},
+    score_replay::ScoreInfo,
+    server_settings::VideoDelivery,
+    status_message::StatusMessage,
+    title_template::title_template,
+    util::{download_osu_file, replay_frames, Frame, MapSections, Metadata},
+    HttpClient, MaxReplayLength, OsuClient, RenderCacheHandler, ReplayHandler, ServerSettings,
 };
 
+/// Messages with more replays than this are refused entirely.
+const MAX_REPLAYS_PER_MESSAGE: usize = 10;
+
+/// Replays of even the longest maps stay well below this many bytes.
+const MAX_OSR_SIZE: u64 = 16 * 1024 * 1024;
+
 pub enum AttachmentParseSuccess {
     NothingToDo,
     BeingProcessed,
+    /// The same replay has been rendered before so its link was sent again
+    AlreadyRendered,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -41,640 +63,871 @@ pub enum AttachmentParseError {
     Parsing(#[from] osu_db::Error),
     #[error("replay is in invalid mode: {0:?}")]
     IncorrectMode(osu_db::Mode),
+    #[error("`{name}` can't be rendered, {rejection}")]
+    Rejected {
+        name: String,
+        rejection: ReplayRejection,
+    },
+    #[error("failed to read zip archive")]
+    Archive(#[from] ZipError),
+    #[error("only up to {0} replays can be sent at once")]
+    TooManyReplays(usize),
+    #[error("`{0}` is too large to be a replay")]
+    TooLarge(String),
+}
+
+/// Reasons for refusing a replay before it enters the queue.
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayRejection {
+    #[error("your replay file doesn't contain a beatmap hash")]
+    MissingHash,
+    #[error("the map of your replay is not submitted or has been updated since")]
+    UnknownMap,
+    #[error("the map of your replay is not an osu!standard map")]
+    MapMode,
+    #[error(
+        "the replay is {} long but only replays up to {} can be rendered",
+        format_secs(*.length),
+        format_secs(*.max)
+    )]
+    TooLong { length: u32, max: u32 },
+    #[error(
+        "`{}` is outside of the map which is only {} long",
+        format_millis(*.point),
+        format_millis(*.length as i64)
+    )]
+    OutsideMap { point: i64, length: u32 },
+    #[error(
+        "the start at {} must be before the end at {}",
+        format_millis(*.start as i64),
+        format_millis(*.end as i64)
+    )]
+    StartAfterEnd { start: u32, end: u32 },
 }
 
 type AttachmentParseResult = Result<AttachmentParseSuccess, AttachmentParseError>;
 
 #[derive(Clone, Debug)]
 pub struct Data {
+    /// `None` if the replay was sent in a DM
+    pub guild: Option<GuildId>,
     pub input_channel: ChannelId,
     pub output_channel: ChannelId,
     pub path: String,
     pub replay: Replay,
     pub time_points: Option<TimePoints>,
     pub user: UserId,
+    pub delivery: VideoDelivery,
+    /// `Some` if the replay was downloaded for an online score
+    pub score: Option<ScoreInfo>,
+    /// Paths of further replays on the same map that are rendered alongside in knockout mode
+    pub knockout: Vec<String>,
+    /// The user's or guild's template for the video title, `None` for the default
+    pub title_template: Option<String>,
+    /// The message in the input channel that shows the replay's status,
+    /// posted once the replay is queued
+    pub status_message: Option<MessageId>,
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct TimePoints {
-    pub start: Option<u32>,
-    pub end: Option<u32>,
-}
+impl Data {
+    /// A readable name of the replay based on its file name
+    pub fn name(&self) -> String {
+        if !self.knockout.is_empty() {
+            return format!("Knockout of {} replays", self.knockout.len() + 1);
+        }
 
-impl TimePoints {
-    pub fn parse_single(s: &str) -> Result<u32, &'static str> {
-        let mut iter = s.split(':').map(str::parse);
-
-        match (iter.next(), iter.next()) {
-            (Some(Ok(minutes)), Some(Ok(seconds @ 0..=59))) => Ok(minutes * 60 + seconds),
-            (Some(Ok(_)), Some(Ok(_))) => Err("Seconds must be between 0 and 60!"),
-            (Some(Ok(seconds)), None) => Ok(seconds),
-            _ => Err("A value you supplied is not a number!"),
+        let file = self.path.trim_start_matches("../Downloads/");
+        let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+        // Attachments are saved with the id of their message and their index in front
+        let file = match file.splitn(3, '_').collect::<Vec<_>>()[..] {
+            [msg_id, idx, name] if is_number(msg_id) && is_number(idx) => name,
+            _ => file,
+        };
+
+        file.replace('_', " ").replace(".osr", "")
+    }
+
+    /// The message that lets the user know that the video is ready
+    pub fn announcement(&self, video: impl Display) -> String {
+        match self.score {
+            Some(ref score) => format!("<@{}> your replay is ready! {video}\n{score}", self.user),
+            None => format!("<@{}> your replay is ready! {video}", self.user),
         }
     }
 }
 
-pub async fn process_replay(osu: Osu, http: Arc<Http>, client: Client, queue: Arc<ReplayQueue>) {
-    let url = env::var("CUSTOM_UPLOAD_URL")
-        .context("missing env variable `CUSTOM_UPLOAD_URL`")
-        .unwrap();
+/// The part of a map that is rendered.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "StoredTimePoints")]
+pub struct TimePoints {
+    /// Milliseconds since the start of the map or, if negative, before its end
+    #[serde(rename = "start_ms")]
+    pub start: Option<i64>,
+    #[serde(rename = "end_ms")]
+    pub end: Option<i64>,
+}
 
-    let secret_key = env::var("CUSTOM_UPLOAD_SECRET")
-        .context("missing env variable `CUSTOM_UPLOAD_SECRET`")
-        .unwrap();
+/// Time points as they are persisted in the queue, which may still contain
+/// replays that were queued while the time points were whole seconds.
+#[derive(Deserialize)]
+struct StoredTimePoints {
+    start_ms: Option<i64>,
+    end_ms: Option<i64>,
+    /// Seconds since the start of the map
+    start: Option<u32>,
+    end: Option<u32>,
+}
 
-    let uploader = CustomUploadApi::new(url, secret_key)
-        .await
-        .context("failed to create custom upload api wrapper")
-        .unwrap();
-
-    loop {
-        let Data {
-            input_channel,
-            output_channel: replay_channel,
-            path: replay_path,
-            replay: replay_file,
-            time_points,
-            user: replay_user,
-        } = queue.peek().await;
-
-        let mapset = match replay_file.beatmap_hash.as_deref() {
-            Some(hash) => match osu.beatmap().checksum(hash).await {
-                Ok(Map { mapset, .. }) => match mapset {
-                    Some(mapset) => mapset,
-                    None => {
-                        warn!("missing mapset in map");
-
-                        send_error_message(
-                            &http,
-                            input_channel,
-                            replay_user,
-                            "the mapset is missing in the map",
-                        )
-                        .await;
-
-                        queue.reset_peek().await;
-                        continue;
-                    }
-                },
-                Err(why) => {
-                    let err = Error::new(why)
-                        .context(format!("failed to request map with hash `{hash}`"));
-                    warn!("{err:?}");
+impl From<StoredTimePoints> for TimePoints {
+    fn from(stored: StoredTimePoints) -> Self {
+        let millis = |secs: Option<u32>| secs.map(|secs| secs as i64 * 1000);
 
-                    send_error_message(
-                        &http,
-                        input_channel,
-                        replay_user,
-                        format!("failed to get the map with hash: `{hash}`"),
-                    )
-                    .await;
+        Self {
+            start: stored.start_ms.or_else(|| millis(stored.start)),
+            end: stored.end_ms.or_else(|| millis(stored.end)),
+        }
+    }
+}
 
-                    queue.reset_peek().await;
-                    continue;
-                }
-            },
-            None => {
-                warn!("No hash in replay requested by user {replay_user}");
-
-                send_error_message(
-                    &http,
-                    input_channel,
-                    replay_user,
-                    "couldn't find hash in your replay file",
-                )
-                .await;
-
-                queue.reset_peek().await;
-                continue;
-            }
+impl TimePoints {
+    /// Parse a time like `90`, `1:30`, `1:23.450` or `1:02:03` into milliseconds.
+    /// A leading `-` counts back from the end of the map.
+    pub fn parse_single(s: &str) -> Result<i64, &'static str> {
+        let (sign, s) = match s.strip_prefix('-') {
+            Some(s) => (-1, s),
+            None => (1, s),
         };
 
-        let mapset_id = mapset.mapset_id;
-        info!("Started map download");
-        queue.set_status(ReplayStatus::Downloading).await;
+        let (whole, fraction) = match s.split_once('.') {
+            // `.5` is half a second
+            Some(("", fraction)) => ("0", Some(fraction)),
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (s, None),
+        };
 
-        if let Err(why) = download_mapset(mapset_id, &client).await {
-            warn!("{:?}", why);
+        let mut parts = whole.split(':');
+        let mut secs = 0_i64;
 
-            send_error_message(
-                &http,
-                input_channel,
-                replay_user,
-                format!("failed to download map: {why}"),
-            )
-            .await;
+        for (i, part) in parts.by_ref().take(3).enumerate() {
+            let value: u32 = part
+                .parse()
+                .map_err(|_| "A value you supplied is not a number!")?;
 
-            queue.reset_peek().await;
-            continue;
-        }
+            if i > 0 && value > 59 {
+                return Err("Minutes and seconds must be between 0 and 60!");
+            }
 
-        info!("Finished map download");
+            secs = secs * 60 + value as i64;
+        }
 
-        let settings = if path_exists(format!("../danser/settings/{replay_user}.json")).await {
-            replay_user.to_string()
-        } else {
-            "default".to_string()
-        };
+        if parts.next().is_some() {
+            return Err("Times must look like `h:mm:ss.mmm`!");
+        }
 
-        let filename_opt = replay_path
-            .split('/')
-            .last()
-            .and_then(|file| file.split('.').next());
-
-        let filename = match filename_opt {
-            Some(name) => name,
-            None => {
-                warn!("replay path `{replay_path}` has an unexpected form");
-
-                send_error_message(
-                    &http,
-                    input_channel,
-                    replay_user,
-                    "there was an error resolving the beatmap path",
-                )
-                .await;
-
-                queue.reset_peek().await;
-                continue;
+        let millis = match fraction {
+            None => 0,
+            Some(fraction)
+                if (1..=3).contains(&fraction.len())
+                    && fraction.bytes().all(|byte| byte.is_ascii_digit()) =>
+            {
+                // `.5` means 500 milliseconds
+                fraction.parse::<i64>().unwrap_or(0) * 10_i64.pow(3 - fraction.len() as u32)
             }
+            Some(_) => return Err("Times can only have up to three decimal places!"),
         };
 
-        let mut command = Command::new("../danser/danser");
-
-        command
-            .arg(format!("-replay={}", replay_path))
-            .arg("-record")
-            .arg(format!("-settings={}", settings))
-            .arg("-quickstart")
-            .arg(format!("-out={}", filename));
+        Ok(sign * (secs * 1000 + millis))
+    }
 
-        if let Some(time_points) = time_points {
-            if let Some(start) = time_points.start {
-                command.args(["-start", &start.to_string()]);
+    /// Start and end in milliseconds on a map that is `length` milliseconds long.
+    pub fn resolve(self, length: u32) -> Result<(u32, u32), ReplayRejection> {
+        let resolve = |point: i64| {
+            let absolute = if point < 0 {
+                length as i64 + point
+            } else {
+                point
+            };
+
+            if (0..=length as i64).contains(&absolute) {
+                Ok(absolute as u32)
+            } else {
+                Err(ReplayRejection::OutsideMap { point, length })
             }
+        };
 
-            if let Some(end) = time_points.end {
-                command.args(["-end", &end.to_string()]);
-            }
+        let start = self.start.map_or(Ok(0), resolve)?;
+        let end = self.end.map_or(Ok(length), resolve)?;
+
+        if start >= end {
+            return Err(ReplayRejection::StartAfterEnd { start, end });
         }
 
-        info!("Started replay parsing");
-        queue.set_status(ReplayStatus::Processing).await;
+        Ok((start, end))
+    }
+}
 
-        match command.output().await {
-            Ok(output) => {
-                if let Ok(stdout) = std::str::from_utf8(&output.stdout) {
-                    debug!("stdout: {}", stdout);
-                }
+/// A replay that was attached to a message, either directly or inside a zip archive.
+pub struct ReplayFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+    pub replay: Replay,
+}
 
-                if let Ok(stderr) = std::str::from_utf8(&output.stderr) {
-                    debug!("stderr: {}", stderr);
+/// Download and parse all `.osr` attachments and the `.osr` files of all `.zip` attachments.
+pub async fn collect_replay_files(
+    attachments: &[Attachment],
+    max: usize,
+) -> Result<Vec<ReplayFile>, AttachmentParseError> {
+    let mut files = Vec::new();
+
+    for attachment in attachments {
+        match attachment.filename.rsplit('.').next() {
+            Some("osr") => {
+                if files.len() == max {
+                    return Err(AttachmentParseError::TooManyReplays(max));
+                } else if attachment.size > MAX_OSR_SIZE {
+                    return Err(AttachmentParseError::TooLarge(attachment.filename.clone()));
                 }
+
+                let bytes = attachment.download().await?;
+                files.push((attachment.filename.clone(), bytes));
             }
-            Err(why) => {
-                let err = Error::new(why).context("failed to get command output");
-                warn!("{:?}", err);
-
-                send_error_message(
-                    &http,
-                    input_channel,
-                    replay_user,
-                    format!("failed to parse replay: {err}"),
-                )
-                .await;
-
-                queue.reset_peek().await;
-                continue;
-            }
-        }
+            Some("zip") => {
+                let bytes = attachment.download().await?;
+                let mut archive = ZipArchive::new(Cursor::new(bytes))?;
 
-        info!("Finished replay parsing");
+                for i in 0..archive.len() {
+                    let file = archive.by_index(i)?;
 
-        let map_osu_file = match get_beatmap_osu_file(mapset_id).await {
-            Ok(osu_file) => osu_file,
-            Err(why) => {
-                warn!("{:?}", why.context("failed to get map_osu_file"));
-
-                send_error_message(
-                    &http,
-                    input_channel,
-                    replay_user,
-                    "danser did not like the replay file",
-                )
-                .await;
-
-                queue.reset_peek().await;
-                continue;
-            }
-        };
+                    if !file.is_file() || !file.name().ends_with(".osr") {
+                        continue;
+                    }
 
-        let map_path = format!("../Songs/{}/{}", mapset_id, map_osu_file);
-        let filepath = format!("../Replays/{}.mp4", filename);
+                    if files.len() == max {
+                        return Err(AttachmentParseError::TooManyReplays(max));
+                    }
 
-        let video_title = match create_title(&replay_file, map_path, &mapset).await {
-            Ok(title) => title,
-            Err(why) => {
-                warn!("{:?}", why.context("failed to create title"));
-
-                send_error_message(
-                    &http,
-                    input_channel,
-                    replay_user,
-                    "there was an error while trying to create the video title",
-                )
-                .await;
-
-                queue.reset_peek().await;
-                continue;
-            }
-        };
+                    // Entries may be nested within directories
+                    let name = file
+                        .name()
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or_default()
+                        .to_owned();
 
-        info!("Started upload to shisha.mezo.xyz");
-        queue.set_status(ReplayStatus::Uploading).await;
+                    // The size in the archive's header can't be trusted so the read is bounded
+                    let mut bytes = Vec::new();
 
-        let link = match uploader
-            .upload_video(video_title, replay_user, &filepath)
-            .await
-        {
-            Ok(response) => {
-                if response.error == 1 {
-                    warn!("failed to upload: {}", response.text);
-                    send_error_message(
-                        &http,
-                        input_channel,
-                        replay_user,
-                        format!("failed to upload: `{}`", response.text).as_str(),
-                    )
-                    .await;
-
-                    queue.reset_peek().await;
-                    continue;
-                } else {
-                    response.text
+                    file.take(MAX_OSR_SIZE + 1)
+                        .read_to_end(&mut bytes)
+                        .with_context(|| format!("failed to read `{name}` of zip archive"))?;
+
+                    if bytes.len() as u64 > MAX_OSR_SIZE {
+                        return Err(AttachmentParseError::TooLarge(name));
+                    }
+
+                    files.push((name, bytes));
                 }
             }
-            Err(why) => {
-                warn!("{:?}", why.context("failed to upload file"));
-
-                send_error_message(
-                    &http,
-                    input_channel,
-                    replay_user,
-                    "failed to upload to custom uploader",
-                )
-                .await;
-
-                queue.reset_peek().await;
-                continue;
-            }
-        };
-
-        info!("Finished upload to shisha.mezo.xyz");
+            Some(_) | None => continue,
+        }
+    }
 
-        let content = format!("<@{replay_user}> your replay is ready! {link}");
+    let mut replays = Vec::with_capacity(files.len());
 
-        let msg_fut = replay_channel.send_message(&http, |m| m.content(content));
+    for (name, bytes) in files {
+        let replay = osu_db::Replay::from_bytes(&bytes)?;
 
-        if let Err(why) = msg_fut.await {
-            let err = Error::new(why).context("failed to send video link");
-            warn!("{:?}", err);
+        if replay.mode != osu_db::Mode::Standard {
+            return Err(AttachmentParseError::IncorrectMode(replay.mode));
         }
 
-        queue.reset_peek().await;
+        replays.push(ReplayFile {
+            name,
+            bytes,
+            replay,
+        });
     }
+
+    Ok(replays)
 }
 
-pub async fn parse_attachment_replay(
+/// A replay of a message together with its frames and map.
+pub struct LoadedReplay {
+    pub name: String,
+    /// The `.osr` file of the replay
+    pub bytes: Vec<u8>,
+    pub replay: Replay,
+    pub frames: Vec<Frame>,
+    pub map: Beatmap,
+    /// The map as it's known to the osu! API
+    pub api_map: Map,
+    pub metadata: Metadata,
+    pub sections: MapSections,
+}
+
+/// Collect the attached replay, decode its frames and download its map.
+///
+/// `None` if that's not possible, in which case the author has been told why.
+/// `action` names what is done with the replay in replies, e.g. "analyze".
+pub async fn load_replay(
+    ctx: &Context,
     msg: &Message,
-    ctx_data: &RwLock<TypeMap>,
-    time_points: Option<TimePoints>,
-) -> AttachmentParseResult {
-    let attachment = match msg.attachments.last() {
-        Some(a) if matches!(a.filename.split('.').last(), Some("osr")) => a,
-        Some(_) | None => return Ok(AttachmentParseSuccess::NothingToDo),
+    action: &str,
+) -> SerenityResult<Option<LoadedReplay>> {
+    let ReplayFile {
+        name,
+        bytes,
+        replay,
+    } = match collect_replay(ctx, msg, action).await? {
+        Some(file) => file,
+        None => return Ok(None),
     };
 
-    let guild_id;
-    let channel_opt;
-    let output_channel;
+    let hash = match replay.beatmap_hash.as_deref() {
+        Some(hash) => hash,
+        None => {
+            msg.reply(&ctx, "Couldn't find the map's hash in your replay file")
+                .await?;
 
-    if msg.is_private() {
-        output_channel = msg.channel_id;
-    } else {
-        guild_id = match msg.guild_id {
-            Some(guild_id) => guild_id,
-            None => return Ok(AttachmentParseSuccess::NothingToDo),
-        };
+            return Ok(None);
+        }
+    };
 
-        channel_opt = {
-            let data = ctx_data.read().await;
-            let settings = data.get::<ServerSettings>().unwrap();
+    let (osu, client) = {
+        let data = ctx.data.read().await;
 
-            settings
-                .servers
-                .get(&guild_id)
-                .filter(|s| s.input_channel == msg.channel_id)
-                .map(|s| s.output_channel)
-        };
+        (
+            Arc::clone(data.get::<OsuClient>().unwrap()),
+            data.get::<HttpClient>().unwrap().clone(),
+        )
+    };
 
-        output_channel = match channel_opt {
-            Some(channel_id) => channel_id,
-            None => return Ok(AttachmentParseSuccess::NothingToDo),
-        };
-    }
+    let unknown_map = "The map of your replay is not submitted or has been updated";
+
+    let api_map = match osu.beatmap().checksum(hash).await {
+        Ok(map) => map,
+        Err(OsuError::NotFound) => {
+            msg.reply(&ctx, unknown_map).await?;
 
-    let bytes = match attachment.download().await {
-        Ok(bytes) => bytes,
-        Err(err) => {
-            warn!("download error: {err}");
-            return Err(AttachmentParseError::Download(err));
+            return Ok(None);
+        }
+        Err(why) => {
+            let err = Error::new(why).context(format!("failed to request map to {action}"));
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(None);
         }
     };
 
-    let replay = match osu_db::Replay::from_bytes(&bytes) {
-        Ok(replay) => replay,
-        Err(err) => {
-            warn!("osu_db replay error: {err}");
-            return Err(AttachmentParseError::Parsing(err));
+    let osu_file = match download_osu_file(&client, api_map.map_id, hash).await {
+        Ok(Some(osu_file)) => osu_file,
+        Ok(None) => {
+            msg.reply(&ctx, unknown_map).await?;
+
+            return Ok(None);
+        }
+        Err(why) => {
+            warn!(
+                "{:?}",
+                why.context(format!("failed to download map to {action}"))
+            );
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(None);
         }
     };
 
-    let replay_mode = replay.mode;
-    if replay_mode != osu_db::Mode::Standard {
-        warn!("replay sent was not in played in std");
-        return Err(AttachmentParseError::IncorrectMode(replay_mode));
-    }
+    let map = match Beatmap::parse(osu_file.as_slice()).await {
+        Ok(map) => map,
+        Err(why) => {
+            let err = Error::new(why).context(format!("failed to parse map to {action}"));
+            warn!("{err:?}");
 
-    let mut file = match File::create(format!("../Downloads/{}", &attachment.filename)).await {
-        Ok(file) => file,
-        Err(err) => {
-            warn!("failed to create file: {err}");
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
 
-            return Err(AttachmentParseError::Other(anyhow!(
-                "failed to create file: {err:?}"
-            )));
+            return Ok(None);
         }
     };
 
-    match file.write_all(&bytes).await {
-        Ok(()) => (),
-        Err(err) => {
-            warn!("failed writing to file");
-            return Err(AttachmentParseError::Other(anyhow!(
-                "failed writing to file: {err:?}"
-            )));
+    // Decompressing the frames takes a while for long replays
+    let frames_res = task::spawn_blocking(move || {
+        let frames = replay_frames(&replay);
+
+        (replay, frames)
+    })
+    .await;
+
+    let (replay, frames) = match frames_res {
+        Ok((replay, Ok(frames))) => (replay, frames),
+        Ok((_, Err(why))) => {
+            let context = format!("failed to read frames of replay to {action}");
+            warn!("{:?}", why.context(context));
+            msg.reply(&ctx, "The replay data of your replay could not be read")
+                .await?;
+
+            return Ok(None);
+        }
+        Err(why) => {
+            let err = Error::new(why).context(format!("failed to decode replay to {action}"));
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(None);
         }
     };
 
-    let replay_data = Data {
-        input_channel: msg.channel_id,
-        output_channel,
-        path: format!("../Downloads/{}", attachment.filename),
+    let content = String::from_utf8_lossy(&osu_file);
+    let metadata = Metadata::parse(&content);
+    let sections = MapSections::parse(&content);
+
+    Ok(Some(LoadedReplay {
+        name,
+        bytes,
         replay,
-        time_points,
-        user: msg.author.id,
+        frames,
+        map,
+        api_map,
+        metadata,
+        sections,
+    }))
+}
+
+/// Like [`load_replay`] but also judges the replay's hits.
+pub async fn load_analyzed_replay(
+    ctx: &Context,
+    msg: &Message,
+    action: &str,
+) -> SerenityResult<Option<(LoadedReplay, Analysis)>> {
+    let loaded = match load_replay(ctx, msg, action).await? {
+        Some(loaded) => loaded,
+        None => return Ok(None),
     };
 
-    ctx_data
-        .read()
-        .await
-        .get::<ReplayHandler>()
-        .unwrap()
-        .push(replay_data)
-        .await;
+    // Judging every object takes a while for long maps
+    let analysis_res = task::spawn_blocking(move || {
+        let analysis = Analysis::new(&loaded.replay, &loaded.frames, &loaded.map);
 
-    Ok(AttachmentParseSuccess::BeingProcessed)
-}
+        (loaded, analysis)
+    })
+    .await;
 
-async fn path_exists(path: String) -> bool {
-    fs::metadata(path).await.is_ok()
+    match analysis_res {
+        Ok(analyzed) => Ok(Some(analyzed)),
+        Err(why) => {
+            let err = Error::new(why).context(format!("failed to analyze replay to {action}"));
+            warn!("{err:?}");
+
+            msg.reply(&ctx, "something went wrong, blame mezo").await?;
+
+            Ok(None)
+        }
+    }
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error(
-    "failed to download mapset\n\
-    <https://chimu.moe> error: {}\n\
-    <https://kitsu.moe> error: {}",
-    kitsu,
-    chimu
-)]
-struct MapsetDownloadError {
-    kitsu: Error,
-    chimu: Error,
-}
-
-async fn download_mapset(mapset_id: u32, client: &Client) -> Result<()> {
-    let out_path = format!("../Songs/{mapset_id}");
-    let url = format!("https://kitsu.moe/d/{mapset_id}");
-
-    let kitsu = match download_mapset_(url, &out_path, client).await {
-        Ok(_) => return Ok(()),
-        Err(why) => why,
-    };
-    debug!("Using secondary mirror");
-    let url = format!("https://chimu.moe/d/{mapset_id}");
+/// Download and parse the single replay that is attached to the message.
+///
+/// `None` if that's not possible, in which case the author has been told why.
+pub async fn collect_replay(
+    ctx: &Context,
+    msg: &Message,
+    action: &str,
+) -> SerenityResult<Option<ReplayFile>> {
+    let content = match collect_replay_files(&msg.attachments, 1).await {
+        Ok(mut files) if !files.is_empty() => return Ok(Some(files.swap_remove(0))),
+        Ok(_) => "You must attach a replay!".to_owned(),
+        Err(
+            why @ (AttachmentParseError::TooManyReplays(_) | AttachmentParseError::TooLarge(_)),
+        ) => {
+            format!("Can't {action} the replays, {why}")
+        }
+        Err(AttachmentParseError::IncorrectMode(_)) => {
+            format!("Can't {action} the replay, only osu!standard is supported :(")
+        }
+        Err(AttachmentParseError::Parsing(_) | AttachmentParseError::Archive(_)) => {
+            "The attachment could not be read".to_owned()
+        }
+        Err(why) => {
+            let err = Error::new(why).context(format!("failed to collect replay to {action}"));
+            warn!("{err:?}");
 
-    let chimu = match download_mapset_(url, &out_path, client).await {
-        Ok(_) => return Ok(()),
-        Err(why) => why,
+            "something went wrong, blame mezo".to_owned()
+        }
     };
 
-    Err(MapsetDownloadError { kitsu, chimu }.into())
+    msg.reply(&ctx, content).await?;
+
+    Ok(None)
 }
 
-async fn download_mapset_(url: String, out_path: &str, client: &Client) -> Result<()> {
-    let bytes = match client.get(&url).send().await {
-        Ok(resp) => match resp.bytes().await {
-            Ok(bytes) => bytes,
-            Err(err) => return Err(anyhow!("failed to read bytes: {err}")),
-        },
-        Err(err) => return Err(anyhow!("failed to GET using: {url}, error: {err}")),
-    };
+pub async fn parse_attachment_replay(
+    ctx: &Context,
+    msg: &Message,
+    time_points: Option<TimePoints>,
+) -> AttachmentParseResult {
+    let has_replays = msg
+        .attachments
+        .iter()
+        .any(|a| matches!(a.filename.split('.').next_back(), Some("osr" | "zip")));
 
-    let cursor = Cursor::new(bytes);
+    if !has_replays {
+        return Ok(AttachmentParseSuccess::NothingToDo);
+    }
 
-    let mut archive = match ZipArchive::new(cursor) {
-        Ok(archive) => archive,
-        Err(err) => return Err(anyhow!("failed to create zip archive: {err}")),
+    let destination = match attachment_destination(ctx, msg).await {
+        Some(destination) => destination,
+        None => return Ok(AttachmentParseSuccess::NothingToDo),
     };
 
-    match archive.extract(out_path) {
-        Ok(()) => (),
-        Err(err) => {
-            return Err(anyhow!(
-                "failed to extract zip archive at `{out_path}`, error: {err}"
-            ))
+    let files = collect_replay_files(&msg.attachments, MAX_REPLAYS_PER_MESSAGE).await?;
+
+    if files.is_empty() {
+        return Ok(AttachmentParseSuccess::NothingToDo);
+    }
+
+    // Either all replays of the message are queued or none
+    for file in files.iter() {
+        if let Err(rejection) = validate_replay(ctx, &file.replay, time_points).await {
+            return Err(AttachmentParseError::Rejected {
+                name: file.name.clone(),
+                rejection,
+            });
         }
-    };
+    }
 
-    Ok(())
+    queue_replay_files(ctx, msg, destination, files, time_points).await
 }
 
-async fn create_title(replay: &Replay, map_path: String, _mapset: &Beatmapset) -> Result<String> {
-    let mods = replay.mods.bits();
+/// Store and queue replays of the message that have already been validated.
+pub async fn queue_replay_files(
+    ctx: &Context,
+    msg: &Message,
+    (guild, output_channel, delivery): (Option<GuildId>, ChannelId, VideoDelivery),
+    files: Vec<ReplayFile>,
+    time_points: Option<TimePoints>,
+) -> AttachmentParseResult {
+    let title_template = title_template(ctx, guild, msg.author.id).await;
+    let mut success = AttachmentParseSuccess::AlreadyRendered;
 
-    let stars = match Beatmap::from_path(&map_path).await {
-        Ok(beatmap) => beatmap.stars(mods, None).stars(),
-        Err(err) => return Err(anyhow!("failed to get stars: {err}")),
-    };
+    // Replays of different messages or of a zip archive may share a file name
+    for (i, file) in files.into_iter().enumerate() {
+        let path = format!("../Downloads/{}_{i}_{}", msg.id, file.name);
 
-    let mods_str = GameMods::from_bits(mods).unwrap_or_default().to_string();
-    let stars = (stars * 100.0).round() / 100.0;
-    let player = replay.player_name.as_deref().unwrap_or_default();
-    let map_title = get_title().await.unwrap();
-    let acc = accuracy(replay, GameMode::STD);
-
-    let title = format!(
-        "[{stars}⭐] {player} | {map_title} {mods}{acc}%",
-        mods = if &mods_str == "NM" {
-            String::new()
-        } else {
-            format!("+{mods_str} ")
-        },
-    );
+        let mut out = File::create(&path)
+            .await
+            .with_context(|| format!("failed to create file `{path}`"))?;
 
-    Ok(title)
+        out.write_all(&file.bytes)
+            .await
+            .with_context(|| format!("failed writing to `{path}`"))?;
+
+        let replay_data = Data {
+            guild,
+            input_channel: msg.channel_id,
+            output_channel,
+            path,
+            replay: file.replay,
+            time_points,
+            user: msg.author.id,
+            delivery,
+            score: None,
+            knockout: Vec::new(),
+            title_template: title_template.clone(),
+            status_message: None,
+        };
+
+        if let AttachmentParseSuccess::BeingProcessed = queue_replay(ctx, replay_data).await {
+            success = AttachmentParseSuccess::BeingProcessed;
+        }
+    }
+
+    Ok(success)
 }
 
-async fn get_beatmap_osu_file(mapset_id: u32) -> Result<String> {
-    let file = match fs::read_to_string("../danser/danser.log").await {
-        Ok(file) => file,
-        Err(err) => return Err(anyhow!("failed to read danser logs: {err}")),
-    };
+/// Let the author of a message know how its replay attachments were handled.
+pub async fn respond_to_replay_attachments(
+    ctx: &Context,
+    msg: &Message,
+    result: AttachmentParseResult,
+) {
+    let content = match result {
+        Ok(AttachmentParseSuccess::NothingToDo) => return,
+        Ok(AttachmentParseSuccess::BeingProcessed | AttachmentParseSuccess::AlreadyRendered) => {
+            let reaction = ReactionType::Unicode("✅".to_string());
+
+            if let Err(why) = msg.react(ctx, reaction).await {
+                let err = Error::new(why).context("failed to react after attachment parse success");
+                warn!("{err:?}");
+            }
 
-    let line = if let Some(l) = file.lines().find(|line| line.contains("Playing:")) {
-        l
-    } else {
-        return Err(anyhow!("expected `Playing:` in danser logs"));
+            return;
+        }
+        Err(AttachmentParseError::Rejected { name, rejection }) => {
+            format!("Your replay `{name}` can't be rendered, {rejection}")
+        }
+        Err(AttachmentParseError::IncorrectMode(_)) => {
+            "danser only accepts osu!standard plays, sorry :(".to_owned()
+        }
+        Err(
+            why @ (AttachmentParseError::TooManyReplays(_) | AttachmentParseError::TooLarge(_)),
+        ) => format!("Sorry, {why}"),
+        Err(AttachmentParseError::Archive(_)) => "Your zip archive could not be read".to_owned(),
+        Err(why) => {
+            let err = Error::new(why).context("failed to parse attachment");
+            warn!("{err:?}");
+
+            "something went wrong, blame mezo".to_owned()
+        }
     };
 
-    let map_without_artist = if let Some(m) = line.splitn(4, ' ').last() {
-        m
-    } else {
-        return Err(anyhow!(
-            "expected at least 5 words in danser log line `{line}`"
-        ));
-    };
+    if let Err(why) = msg.reply(ctx, content).await {
+        let err = Error::new(why).context("failed to reply after attachment parse error");
+        warn!("{err:?}");
+    }
+}
 
-    let items_dir = format!("../Songs/{}", mapset_id);
+/// Push the replay into the queue unless it has been rendered before,
+/// in which case the previous video is sent again.
+pub async fn queue_replay(ctx: &Context, mut replay_data: Data) -> AttachmentParseSuccess {
+    let (renders, queue) = {
+        let data = ctx.data.read().await;
 
-    let mut items = match fs::read_dir(&items_dir).await {
-        Ok(items) => items,
-        Err(err) => {
-            return Err(anyhow!(
-                "failed to read items dir at `{items_dir}`, error: {err}"
-            ))
-        }
+        (
+            Arc::clone(data.get::<RenderCacheHandler>().unwrap()),
+            Arc::clone(data.get::<ReplayHandler>().unwrap()),
+        )
     };
 
-    let mut correct_items: Vec<DirEntry> = Vec::new();
+    match RenderKey::new(&replay_data).await {
+        Ok(key) => {
+            if let Some(link) = renders.get(&key) {
+                let content = replay_data.announcement(link);
+
+                let msg_fut = replay_data
+                    .output_channel
+                    .send_message(ctx, |m| m.content(content));
 
-    loop {
-        match items.next_entry().await {
-            Ok(Some(entry)) => {
-                if entry.file_name().to_str().unwrap().ends_with(".osu") {
-                    correct_items.push(entry);
+                if let Err(why) = msg_fut.await {
+                    let err = Error::new(why).context("failed to send cached video link");
+                    warn!("{err:?}");
                 }
-            }
-            Ok(None) => break,
-            Err(err) => {
-                return Err(anyhow!(
-                    "there was an error while trying to read the files: {err}"
-                ))
+
+                return AttachmentParseSuccess::AlreadyRendered;
             }
         }
+        Err(why) => warn!("{:?}", why.context("failed to create render key")),
     }
 
-    let mut max_similarity: f32 = 0.0;
-    let mut final_file_name = String::new();
-
-    for item in correct_items {
-        let file_name = item.file_name();
-        let item_file_name = file_name.to_string_lossy();
+    replay_data.status_message = StatusMessage::post(&ctx.http, &replay_data).await;
+    queue.push(replay_data).await;
 
-        debug!("COMPARING: {map_without_artist} WITH: {item_file_name}");
-
-        let similarity = levenshtein_similarity(map_without_artist, &item_file_name);
+    AttachmentParseSuccess::BeingProcessed
+}
 
-        if similarity > max_similarity {
-            max_similarity = similarity;
-            final_file_name = item_file_name.into_owned();
-        }
+/// The guild, output channel and delivery for replays that are attached in the message's channel.
+///
+/// `None` if replays are not accepted in the channel.
+pub async fn attachment_destination(
+    ctx: &Context,
+    msg: &Message,
+) -> Option<(Option<GuildId>, ChannelId, VideoDelivery)> {
+    if msg.is_private() {
+        return Some((None, msg.channel_id, VideoDelivery::default()));
     }
 
-    debug!("FINAL TITLE: {final_file_name} SIMILARITY: {max_similarity}");
+    let guild_id = msg.guild_id?;
+    let data = ctx.data.read().await;
+    let settings = data.get::<ServerSettings>().unwrap();
 
-    Ok(final_file_name)
+    settings
+        .servers
+        .get(&guild_id)
+        .filter(|s| s.input_channel == msg.channel_id)
+        .map(|s| (Some(guild_id), s.output_channel, s.delivery))
 }
 
-fn accuracy(replay: &Replay, mode: GameMode) -> f32 {
-    let amount_objects = total_hits(replay, mode) as f32;
+/// The guild, output channel and delivery for a replay that is submitted through a command.
+///
+/// `None` if the command was used in a server that has no output channel yet.
+pub async fn command_destination(
+    ctx: &Context,
+    msg: &Message,
+) -> Option<(Option<GuildId>, ChannelId, VideoDelivery)> {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Some((None, msg.channel_id, VideoDelivery::default())),
+    };
 
-    let (numerator, denumerator) = match mode {
-        GameMode::TKO => (
-            0.5 * replay.count_100 as f32 + replay.count_300 as f32,
-            amount_objects,
-        ),
-        GameMode::CTB => (
-            (replay.count_300 + replay.count_100 + replay.count_50) as f32,
-            amount_objects,
-        ),
-        GameMode::STD | GameMode::MNA => {
-            let mut n = (replay.count_50 as u32 * 50
-                + replay.count_100 as u32 * 100
-                + replay.count_300 as u32 * 300) as f32;
+    let data = ctx.data.read().await;
+    let settings = data.get::<ServerSettings>().unwrap();
 
-            n += ((mode == GameMode::MNA) as u32
-                * (replay.count_katsu as u32 + replay.count_geki as u32))
-                as f32;
+    settings
+        .servers
+        .get(&guild_id)
+        .filter(|s| s.output_channel != ChannelId(0))
+        .map(|s| (Some(guild_id), s.output_channel, s.delivery))
+}
 
-            (n, amount_objects * 300.0)
+/// Check that the replay's map can be rendered before the replay is queued.
+pub async fn validate_replay(
+    ctx: &Context,
+    replay: &Replay,
+    time_points: Option<TimePoints>,
+) -> Result<(), ReplayRejection> {
+    let hash = replay
+        .beatmap_hash
+        .as_deref()
+        .ok_or(ReplayRejection::MissingHash)?;
+
+    let osu = Arc::clone(ctx.data.read().await.get::<OsuClient>().unwrap());
+
+    let map = match osu.beatmap().checksum(hash).await {
+        Ok(map) => map,
+        Err(OsuError::NotFound) => return Err(ReplayRejection::UnknownMap),
+        Err(why) => {
+            // The worker will try again so the replay is not rejected
+            let err = Error::new(why).context(format!("failed to request map with hash `{hash}`"));
+            warn!("{err:?}");
+
+            return Ok(());
         }
     };
 
-    (10_000.0 * numerator / denumerator).round() / 100.0
+    validate_replay_map(ctx, replay, &map, time_points).await
 }
 
-fn total_hits(replay: &Replay, mode: GameMode) -> u32 {
-    let mut amount = (replay.count_300 + replay.count_100 + replay.count_miss) as u32;
+/// Like [`validate_replay`] but for a replay whose map has already been requested.
+pub async fn validate_replay_map(
+    ctx: &Context,
+    replay: &Replay,
+    map: &Map,
+    time_points: Option<TimePoints>,
+) -> Result<(), ReplayRejection> {
+    if map.mode != GameMode::STD {
+        return Err(ReplayRejection::MapMode);
+    }
+
+    let hash = replay
+        .beatmap_hash
+        .as_deref()
+        .ok_or(ReplayRejection::MissingHash)?;
 
-    if mode != GameMode::TKO {
-        amount += replay.count_50 as u32;
+    let client = ctx.data.read().await.get::<HttpClient>().unwrap().clone();
 
-        if mode != GameMode::STD {
-            amount += replay.count_katsu as u32;
-            amount += (mode != GameMode::CTB) as u32 * replay.count_geki as u32;
-        }
+    let trim = match time_points {
+        Some(time_points) => match last_object_end(&client, map.map_id, hash).await {
+            Ok(Some(end)) => Some(time_points.resolve(end)?),
+            Ok(None) => return Err(ReplayRejection::UnknownMap),
+            Err(why) => {
+                // The worker will check the time points again so the replay is not rejected
+                warn!(
+                    "{:?}",
+                    why.context("failed to get map to check time points")
+                );
+
+                None
+            }
+        },
+        None => None,
+    };
+
+    check_length(ctx, replay, map, trim).await
+}
+
+/// Like [`validate_replay`] but for a replay whose map has already been downloaded.
+pub async fn validate_loaded_replay(
+    ctx: &Context,
+    loaded: &LoadedReplay,
+    time_points: Option<TimePoints>,
+) -> Result<(), ReplayRejection> {
+    if loaded.api_map.mode != GameMode::STD {
+        return Err(ReplayRejection::MapMode);
     }
 
-    amount
+    let trim = time_points
+        .map(|time_points| time_points.resolve(map_end(&loaded.map)))
+        .transpose()?;
+
+    check_length(ctx, &loaded.replay, &loaded.api_map, trim).await
 }
 
-async fn get_title() -> Result<String> {
-    let file = match fs::read_to_string("../danser/danser.log").await {
-        Ok(file) => file,
-        Err(err) => return Err(anyhow!("failed to read danser logs: {err}")),
-    };
+async fn check_length(
+    ctx: &Context,
+    replay: &Replay,
+    map: &Map,
+    trim: Option<(u32, u32)>,
+) -> Result<(), ReplayRejection> {
+    let max = *ctx.data.read().await.get::<MaxReplayLength>().unwrap();
+    let length = replay_length(map.seconds_drain, replay, trim);
+
+    if length > max {
+        return Err(ReplayRejection::TooLong { length, max });
+    }
 
-    let line = if let Some(l) = file.lines().find(|line| line.contains("Playing:")) {
-        l
-    } else {
-        return Err(anyhow!("expected `Playing:` in danser logs"));
+    Ok(())
+}
+
+/// Milliseconds until the last object of the map has ended which, unlike the
+/// map's length in the API, is what time points are resolved against.
+///
+/// `None` if the map has been updated since.
+async fn last_object_end(client: &reqwest::Client, map_id: u32, hash: &str) -> Result<Option<u32>> {
+    let bytes = match download_osu_file(client, map_id, hash).await? {
+        Some(bytes) => bytes,
+        None => return Ok(None),
     };
 
-    let map_without_artist = if let Some(m) = line.splitn(4, ' ').last() {
-        m
+    let beatmap = Beatmap::parse(bytes.as_slice())
+        .await
+        .with_context(|| format!("failed to parse map {map_id}"))?;
+
+    Ok(Some(map_end(&beatmap)))
+}
+
+/// Seconds that are rendered for the replay, either the trimmed part in milliseconds
+/// or the map's drain time in seconds, taking the speed of the replay's mods into account.
+pub fn replay_length(drain: u32, replay: &Replay, trim: Option<(u32, u32)>) -> u32 {
+    let (start, end) = trim.unwrap_or((0, drain * 1000));
+
+    let mods = GameMods::from_bits(replay.mods.bits()).unwrap_or_default();
+
+    let clock_rate = if mods.contains(GameMods::DoubleTime) {
+        1.5
+    } else if mods.contains(GameMods::HalfTime) {
+        0.75
     } else {
-        return Err(anyhow!(
-            "expected at least 5 words in danser log line `{line}`"
-        ));
+        1.0
     };
 
-    Ok(map_without_artist.to_string())
+    ((end - start) as f32 / 1000.0 / clock_rate) as u32
 }
 
-async fn send_error_message(
+/// Format seconds as `m:ss`.
+pub fn format_secs(secs: u32) -> String {
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Format milliseconds as `m:ss`, adding the milliseconds only if there are any.
+pub fn format_millis(millis: i64) -> String {
+    let sign = if millis < 0 { "-" } else { "" };
+    let millis = millis.unsigned_abs();
+    let secs = format_secs((millis / 1000) as u32);
+
+    match millis % 1000 {
+        0 => format!("{sign}{secs}"),
+        rest => format!("{sign}{secs}.{rest:03}"),
+    }
+}
+
+pub async fn path_exists(path: impl AsRef<Path>) -> bool {
+    fs::metadata(path).await.is_ok()
+}
+
+pub async fn send_error_message(
     http: &Http,
     channel: ChannelId,
     replay_user: UserId,
@@ -687,3 +940,132 @@ async fn send_error_message(
         warn!("Couldn't send error message to discord: {err}");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(TimePoints::parse_single("60"), Ok(60_000));
+        assert_eq!(TimePoints::parse_single("1:30"), Ok(90_000));
+        assert_eq!(TimePoints::parse_single("1:23.450"), Ok(83_450));
+        assert_eq!(TimePoints::parse_single("1:23.45"), Ok(83_450));
+        assert_eq!(TimePoints::parse_single("1:02:03"), Ok(3_723_000));
+        assert_eq!(TimePoints::parse_single("-30"), Ok(-30_000));
+        assert_eq!(TimePoints::parse_single("-0:01.5"), Ok(-1_500));
+        assert_eq!(TimePoints::parse_single(".5"), Ok(500));
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        assert!(TimePoints::parse_single("1:02:03:04").is_err());
+        assert!(TimePoints::parse_single("1:60").is_err());
+        assert!(TimePoints::parse_single("1.2345").is_err());
+        assert!(TimePoints::parse_single("1.").is_err());
+        assert!(TimePoints::parse_single("abc").is_err());
+        assert!(TimePoints::parse_single("").is_err());
+        assert!(TimePoints::parse_single("--5").is_err());
+    }
+
+    #[test]
+    fn resolves_time_points() {
+        let time_points = |start, end| TimePoints { start, end };
+
+        assert_eq!(
+            time_points(None, None).resolve(90_000).ok(),
+            Some((0, 90_000))
+        );
+        assert_eq!(
+            time_points(Some(10_000), Some(-30_000))
+                .resolve(90_000)
+                .ok(),
+            Some((10_000, 60_000))
+        );
+        assert_eq!(
+            time_points(Some(-20_000), None).resolve(90_000).ok(),
+            Some((70_000, 90_000))
+        );
+    }
+
+    #[test]
+    fn rejects_start_after_end() {
+        let time_points = TimePoints {
+            start: Some(60_000),
+            end: Some(30_000),
+        };
+
+        assert!(matches!(
+            time_points.resolve(90_000),
+            Err(ReplayRejection::StartAfterEnd {
+                start: 60_000,
+                end: 30_000
+            })
+        ));
+
+        let time_points = TimePoints {
+            start: Some(-10_000),
+            end: Some(80_000),
+        };
+
+        assert!(matches!(
+            time_points.resolve(90_000),
+            Err(ReplayRejection::StartAfterEnd { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_points_outside_of_map() {
+        let after_end = TimePoints {
+            start: Some(95_000),
+            end: None,
+        };
+
+        assert!(matches!(
+            after_end.resolve(90_000),
+            Err(ReplayRejection::OutsideMap { point: 95_000, .. })
+        ));
+
+        let before_start = TimePoints {
+            start: None,
+            end: Some(-100_000),
+        };
+
+        assert!(matches!(
+            before_start.resolve(90_000),
+            Err(ReplayRejection::OutsideMap { .. })
+        ));
+    }
+
+    #[test]
+    fn resolves_up_to_last_object() {
+        let at_end = TimePoints {
+            start: Some(60_000),
+            end: Some(90_000),
+        };
+
+        assert_eq!(at_end.resolve(90_000).ok(), Some((60_000, 90_000)));
+
+        let after_end = TimePoints {
+            start: Some(60_000),
+            end: Some(90_001),
+        };
+
+        assert!(matches!(
+            after_end.resolve(90_000),
+            Err(ReplayRejection::OutsideMap { point: 90_001, .. })
+        ));
+    }
+
+    #[test]
+    fn migrates_time_points_in_seconds() {
+        let legacy: TimePoints = serde_json::from_str(r#"{"start":30,"end":90}"#).unwrap();
+
+        assert_eq!((legacy.start, legacy.end), (Some(30_000), Some(90_000)));
+
+        let current: TimePoints =
+            serde_json::from_str(r#"{"start_ms":-1500,"end_ms":null}"#).unwrap();
+
+        assert_eq!((current.start, current.end), (Some(-1_500), None));
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Error, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, sync::Mutex as AsyncMutex};
+
+use crate::{
+    process_replays::{path_exists, Data},
+    util::write_atomically,
+};
+
+const CACHE_PATH: &str = "src/render_cache.json";
+
+/// Links of older renders are not reused since uploads might have expired by then.
+const MAX_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Remembers the links of finished renders so identical jobs don't have to be rendered again.
+pub struct RenderCache {
+    renders: Mutex<Renders>,
+    /// Workers finish concurrently but only one of them may write the cache file at a time
+    persisting: AsyncMutex<()>,
+}
+
+type Renders = HashMap<String, CachedRender>;
+
+#[derive(Clone, Deserialize, Serialize)]
+struct CachedRender {
+    link: String,
+    /// Unix timestamp in seconds
+    created: u64,
+}
+
+/// Identifies a render by the replay, the danser settings and the time points.
+pub struct RenderKey(String);
+
+impl RenderKey {
+    /// Create the key of the render that `data` would currently produce.
+    pub async fn new(data: &Data) -> Result<Self> {
+        let replay_hash = match data.replay.replay_hash.as_deref() {
+            Some(hash) => hash.to_owned(),
+            None => {
+                let bytes = fs::read(&data.path)
+                    .await
+                    .with_context(|| format!("failed to read `{}`", data.path))?;
+
+                format!("{:x}", md5::compute(bytes))
+            }
+        };
+
+        // Knockout renders also depend on all further replays
+        let mut knockout_hashes = String::new();
+
+        for path in data.knockout.iter() {
+            let bytes = fs::read(path)
+                .await
+                .with_context(|| format!("failed to read `{path}`"))?;
+
+            knockout_hashes.push_str(&format!("{:x}", md5::compute(bytes)));
+        }
+
+        let user_settings = format!("../danser/settings/{}.json", data.user);
+
+        let settings_path = if path_exists(&user_settings).await {
+            user_settings
+        } else {
+            "../danser/settings/default.json".to_owned()
+        };
+
+        // danser falls back to its own defaults if there is no settings file
+        let settings_hash = match fs::read(&settings_path).await {
+            Ok(bytes) => format!("{:x}", md5::compute(bytes)),
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(Error::new(err).context(format!("failed to read `{settings_path}`")))
+            }
+        };
+
+        let (start, end) = data.time_points.map_or((None, None), |time_points| {
+            (time_points.start, time_points.end)
+        });
+
+        let key = format!(
+            "{replay_hash}{knockout_hashes}:{settings_hash}:{}:{}",
+            start.map_or_else(String::new, |start| start.to_string()),
+            end.map_or_else(String::new, |end| end.to_string()),
+        );
+
+        Ok(Self(key))
+    }
+}
+
+impl RenderCache {
+    pub async fn load() -> Result<Self> {
+        let mut renders: Renders = match fs::read_to_string(CACHE_PATH).await {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(renders) => renders,
+                // Losing the cache only means that some replays are rendered again
+                Err(why) => {
+                    let err = Error::new(why).context(format!(
+                        "failed to deserialize `{CACHE_PATH}`, resetting it"
+                    ));
+                    warn!("{err:?}");
+
+                    HashMap::new()
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(err) => {
+                return Err(Error::new(err).context(format!("failed to read `{CACHE_PATH}`")))
+            }
+        };
+
+        let now = unix_secs();
+        renders.retain(|_, render| now.saturating_sub(render.created) < MAX_AGE_SECS);
+
+        Ok(Self {
+            renders: Mutex::new(renders),
+            persisting: AsyncMutex::new(()),
+        })
+    }
+
+    /// The link of a previous render with the same key.
+    pub fn get(&self, key: &RenderKey) -> Option<String> {
+        let renders = self.renders.lock().unwrap();
+
+        renders
+            .get(&key.0)
+            .filter(|render| unix_secs().saturating_sub(render.created) < MAX_AGE_SECS)
+            .map(|render| render.link.clone())
+    }
+
+    pub async fn insert(&self, key: RenderKey, link: String) {
+        let render = CachedRender {
+            link,
+            created: unix_secs(),
+        };
+
+        self.renders.lock().unwrap().insert(key.0, render);
+        self.persist().await;
+    }
+
+    async fn persist(&self) {
+        let _persisting = self.persisting.lock().await;
+
+        // Serialized while holding the lock so that the latest state is written last
+        let content = {
+            let renders = self.renders.lock().unwrap();
+
+            match serde_json::to_string(&*renders) {
+                Ok(content) => content,
+                Err(why) => {
+                    let err = Error::new(why).context("failed to serialize render cache");
+                    warn!("{err:?}");
+
+                    return;
+                }
+            }
+        };
+
+        if let Err(err) = write_atomically(CACHE_PATH, content).await {
+            warn!("{:?}", err.context("failed to persist render cache"));
+        }
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
@@ -0,0 +1,435 @@
+use osu_db::Replay;
+use rosu_pp::{
+    parse::{HitObject, HitObjectKind, Pos2},
+    Beatmap, Mods,
+};
+
+use crate::util::Frame;
+
+/// Pressing this many milliseconds or less before an object that can't be hit yet is a miss.
+const MISS_WINDOW: f64 = 400.0;
+/// Releasing the keys this close to the end of a slider doesn't break it.
+const SLIDER_LENIENCY: f64 = 36.0;
+/// The map is split into this many parts of equal duration for the per-section accuracy.
+const SECTIONS: usize = 4;
+
+const PLAYFIELD_HEIGHT: f32 = 384.0;
+
+const MOUSE_LEFT: u32 = 1 << 0;
+const MOUSE_RIGHT: u32 = 1 << 1;
+const KEY_LEFT: u32 = 1 << 2;
+const KEY_RIGHT: u32 = 1 << 3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Judgement {
+    Great,
+    Ok,
+    Meh,
+    Miss,
+}
+
+impl Judgement {
+    fn score(self) -> u32 {
+        match self {
+            Self::Great => 300,
+            Self::Ok => 100,
+            Self::Meh => 50,
+            Self::Miss => 0,
+        }
+    }
+}
+
+/// How an object of the map was hit.
+#[derive(Copy, Clone, Debug)]
+pub struct HitResult {
+    /// Start time of the object in milliseconds
+    pub time: f64,
+    pub judgement: Judgement,
+    /// Milliseconds the object was hit too late, negative if it was too early
+    pub error: Option<f64>,
+}
+
+/// Accuracy in a part of the map.
+#[derive(Copy, Clone, Debug)]
+pub struct Section {
+    /// Milliseconds since the start of the map
+    pub start: f64,
+    pub end: f64,
+    pub accuracy: f64,
+    pub misses: usize,
+}
+
+/// Key presses of each osu!standard button.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KeyUsage {
+    pub k1: usize,
+    pub k2: usize,
+    pub m1: usize,
+    pub m2: usize,
+}
+
+/// Statistics of a replay that are estimated by replaying its frames on the map.
+///
+/// Hits are judged by the heads of sliders and object stacking is not taken into
+/// account so the results can differ slightly from the actual play.
+pub struct Analysis {
+    /// One result for each circle and slider
+    pub hits: Vec<HitResult>,
+    /// Scaled by the clock rate like osu! does
+    pub unstable_rate: f64,
+    /// Mean of the hit errors scaled by the clock rate, negative if the player tends to hit early
+    pub mean_error: f64,
+    pub early_hits: usize,
+    pub late_hits: usize,
+    pub sections: Vec<Section>,
+    pub slider_breaks: usize,
+    pub keys: KeyUsage,
+    pub windows: HitWindows,
+    /// In osu!pixels per second of real time
+    pub average_cursor_speed: f64,
+    /// The speed that was only exceeded in 1% of the frames
+    pub peak_cursor_speed: f64,
+}
+
+impl Analysis {
+    pub fn new(replay: &Replay, frames: &[Frame], map: &Beatmap) -> Self {
+        let mods = replay.mods.bits();
+        let attributes = map.attributes().mods(mods);
+        let clock_rate = attributes.clock_rate;
+        let radius = (54.4 - 4.48 * attributes.cs) as f32;
+
+        let windows = HitWindows {
+            great: 80.0 - 6.0 * attributes.od,
+            ok: 140.0 - 8.0 * attributes.od,
+            meh: 200.0 - 10.0 * attributes.od,
+        };
+
+        // Objects are flipped vertically by hard rock
+        let flip = mods.hr();
+
+        let objects: Vec<_> = map
+            .hit_objects
+            .iter()
+            .filter(|object| !object.is_spinner())
+            .map(|object| {
+                let mut pos = object.pos;
+
+                if flip {
+                    pos.y = PLAYFIELD_HEIGHT - pos.y;
+                }
+
+                (object, pos)
+            })
+            .collect();
+
+        let (hits, keys) = judge(&objects, frames, radius, &windows);
+        let errors: Vec<_> = hits.iter().filter_map(|hit| hit.error).collect();
+
+        let (mean_error, unstable_rate) = if errors.is_empty() {
+            (0.0, 0.0)
+        } else {
+            let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+
+            let variance = errors
+                .iter()
+                .map(|error| (error - mean).powi(2))
+                .sum::<f64>()
+                / errors.len() as f64;
+
+            (mean / clock_rate, variance.sqrt() * 10.0 / clock_rate)
+        };
+
+        let slider_breaks = objects
+            .iter()
+            .zip(hits.iter())
+            .filter(|(_, hit)| hit.judgement != Judgement::Miss)
+            .filter(|((object, _), hit)| match slider_end(map, object) {
+                Some(end) => released_early(frames, hit.time + hit.error.unwrap_or(0.0), end),
+                None => false,
+            })
+            .count();
+
+        let (average_cursor_speed, peak_cursor_speed) = match (objects.first(), objects.last()) {
+            (Some((first, _)), Some((last, _))) => {
+                let end = slider_end(map, last).unwrap_or(last.start_time);
+
+                cursor_speed(frames, first.start_time, end, clock_rate)
+            }
+            _ => (0.0, 0.0),
+        };
+
+        Self {
+            sections: sections(&hits),
+            early_hits: errors.iter().filter(|error| **error < 0.0).count(),
+            late_hits: errors.iter().filter(|error| **error >= 0.0).count(),
+            hits,
+            unstable_rate,
+            mean_error,
+            slider_breaks,
+            keys,
+            windows,
+            average_cursor_speed,
+            peak_cursor_speed,
+        }
+    }
+
+    /// Amounts of 300s, 100s, 50s and misses.
+    pub fn judgement_counts(&self) -> [usize; 4] {
+        let mut counts = [0; 4];
+
+        for hit in self.hits.iter() {
+            match hit.judgement {
+                Judgement::Great => counts[0] += 1,
+                Judgement::Ok => counts[1] += 1,
+                Judgement::Meh => counts[2] += 1,
+                Judgement::Miss => counts[3] += 1,
+            }
+        }
+
+        counts
+    }
+}
+
+/// Largest hit errors in milliseconds for each judgement.
+#[derive(Copy, Clone, Debug)]
+pub struct HitWindows {
+    pub great: f64,
+    pub ok: f64,
+    pub meh: f64,
+}
+
+impl HitWindows {
+    fn judge(&self, error: f64) -> Judgement {
+        match error.abs() {
+            error if error <= self.great => Judgement::Great,
+            error if error <= self.ok => Judgement::Ok,
+            _ => Judgement::Meh,
+        }
+    }
+}
+
+/// Judge each object by the key presses, only allowing to hit the oldest object that is
+/// not judged yet, and count the presses of each key.
+fn judge(
+    objects: &[(&HitObject, Pos2)],
+    frames: &[Frame],
+    radius: f32,
+    windows: &HitWindows,
+) -> (Vec<HitResult>, KeyUsage) {
+    let mut hits = Vec::with_capacity(objects.len());
+    let mut keys = KeyUsage::default();
+    let mut prev_buttons = 0;
+
+    let miss = |(object, _): &(&HitObject, Pos2)| HitResult {
+        time: object.start_time,
+        judgement: Judgement::Miss,
+        error: None,
+    };
+
+    for frame in frames {
+        let pressed = frame.buttons & !prev_buttons;
+        prev_buttons = frame.buttons;
+
+        let mut presses = 0;
+
+        if pressed & MOUSE_LEFT != 0 {
+            presses += 1;
+
+            if frame.buttons & KEY_LEFT != 0 {
+                keys.k1 += 1;
+            } else {
+                keys.m1 += 1;
+            }
+        }
+
+        if pressed & MOUSE_RIGHT != 0 {
+            presses += 1;
+
+            if frame.buttons & KEY_RIGHT != 0 {
+                keys.k2 += 1;
+            } else {
+                keys.m2 += 1;
+            }
+        }
+
+        let time = frame.time as f64;
+
+        for _ in 0..presses {
+            // Objects whose window passed without being hit are missed
+            while let Some(object) = objects.get(hits.len()) {
+                if time > object.0.start_time + windows.meh {
+                    hits.push(miss(object));
+                } else {
+                    break;
+                }
+            }
+
+            let object = match objects.get(hits.len()) {
+                Some(object) => object,
+                None => break,
+            };
+
+            let (hit_object, pos) = object;
+            let error = time - hit_object.start_time;
+            let cursor = Pos2 {
+                x: frame.x,
+                y: frame.y,
+            };
+
+            if cursor.distance(*pos) > radius || error < -MISS_WINDOW {
+                continue;
+            }
+
+            let hit = if error < -windows.meh {
+                miss(object)
+            } else {
+                HitResult {
+                    time: hit_object.start_time,
+                    judgement: windows.judge(error),
+                    error: Some(error),
+                }
+            };
+
+            hits.push(hit);
+        }
+    }
+
+    hits.extend(objects[hits.len()..].iter().map(miss));
+
+    (hits, keys)
+}
+
+/// End time of the slider or `None` if the object is not a slider.
+pub fn slider_end(map: &Beatmap, object: &HitObject) -> Option<f64> {
+    let (pixel_len, repeats) = match object.kind {
+        HitObjectKind::Slider {
+            pixel_len, repeats, ..
+        } => (pixel_len, repeats),
+        _ => return None,
+    };
+
+    let time = object.start_time;
+
+    let timing_point = map
+        .timing_points
+        .iter()
+        .take_while(|point| point.time <= time)
+        .last()
+        .or_else(|| map.timing_points.first());
+
+    let beat_len = timing_point.map_or(1000.0, |point| point.beat_len);
+
+    // Uninherited timing points reset the slider velocity
+    let speed_multiplier = map
+        .difficulty_points
+        .iter()
+        .take_while(|point| point.time <= time)
+        .last()
+        .filter(|point| timing_point.is_none_or(|timing| point.time >= timing.time))
+        .map_or(1.0, |point| point.speed_multiplier);
+
+    let span_duration = pixel_len / (100.0 * map.slider_mult * speed_multiplier) * beat_len;
+
+    Some(time + span_duration * (repeats + 1) as f64)
+}
+
+/// Milliseconds until the last object of the map has ended.
+pub fn map_end(map: &Beatmap) -> u32 {
+    map.hit_objects
+        .iter()
+        .map(|object| slider_end(map, object).unwrap_or_else(|| object.end_time()))
+        .fold(0.0, f64::max) as u32
+}
+
+/// Whether all buttons were released between the hit of a slider and its end.
+fn released_early(frames: &[Frame], hit: f64, end: f64) -> bool {
+    let start = frames.partition_point(|frame| (frame.time as f64) < hit);
+
+    frames[start..]
+        .iter()
+        .take_while(|frame| (frame.time as f64) < end - SLIDER_LENIENCY)
+        .any(|frame| frame.buttons & (MOUSE_LEFT | MOUSE_RIGHT) == 0)
+}
+
+/// Average and peak speed of the cursor between `start` and `end`.
+fn cursor_speed(frames: &[Frame], start: f64, end: f64, clock_rate: f64) -> (f64, f64) {
+    let mut distance = 0.0;
+    let mut duration = 0.0;
+    let mut speeds = Vec::new();
+
+    let frames: Vec<_> = frames
+        .iter()
+        .filter(|frame| (start..=end).contains(&(frame.time as f64)))
+        .collect();
+
+    for pair in frames.windows(2) {
+        let delta = (pair[1].time - pair[0].time) as f64 / clock_rate;
+
+        if delta <= 0.0 {
+            continue;
+        }
+
+        let dist = Pos2 {
+            x: pair[0].x,
+            y: pair[0].y,
+        }
+        .distance(Pos2 {
+            x: pair[1].x,
+            y: pair[1].y,
+        }) as f64;
+
+        distance += dist;
+        duration += delta;
+        speeds.push(dist / delta * 1000.0);
+    }
+
+    if speeds.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    speeds.sort_unstable_by(f64::total_cmp);
+    let peak = speeds[(speeds.len() * 99 / 100).min(speeds.len() - 1)];
+
+    (distance / duration * 1000.0, peak)
+}
+
+fn sections(hits: &[HitResult]) -> Vec<Section> {
+    let (first, last) = match (hits.first(), hits.last()) {
+        (Some(first), Some(last)) => (first.time, last.time),
+        _ => return Vec::new(),
+    };
+
+    let len = (last - first) / SECTIONS as f64;
+
+    (0..SECTIONS)
+        .map(|i| {
+            let start = first + len * i as f64;
+            let end = start + len;
+
+            let hits: Vec<_> = hits
+                .iter()
+                .filter(|hit| {
+                    hit.time >= start && (hit.time < end || (i == SECTIONS - 1 && hit.time <= end))
+                })
+                .collect();
+
+            let score: u32 = hits.iter().map(|hit| hit.judgement.score()).sum();
+
+            let accuracy = if hits.is_empty() {
+                100.0
+            } else {
+                score as f64 / (hits.len() as f64 * 300.0) * 100.0
+            };
+
+            Section {
+                start,
+                end,
+                accuracy,
+                misses: hits
+                    .iter()
+                    .filter(|hit| hit.judgement == Judgement::Miss)
+                    .count(),
+            }
+        })
+        .collect()
+}
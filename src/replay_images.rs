@@ -0,0 +1,220 @@
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+
+use crate::{replay_analysis::Analysis, util::Frame};
+
+const PLAYFIELD_WIDTH: usize = 512;
+const PLAYFIELD_HEIGHT: usize = 384;
+/// osu!pixels around the playfield that are shown in the heatmap since the cursor can leave it.
+const MARGIN: usize = 64;
+/// Frames that stay longer than this many milliseconds don't add more heat, e.g. in breaks.
+const MAX_FRAME_WEIGHT: f32 = 50.0;
+const BLUR_RADIUS: usize = 5;
+
+const HISTOGRAM_WIDTH: usize = 800;
+const HISTOGRAM_HEIGHT: usize = 300;
+const HISTOGRAM_BINS: usize = 80;
+
+const BACKGROUND: [u8; 3] = [16, 16, 24];
+const BORDER: [u8; 3] = [90, 90, 100];
+
+/// Colors of the heatmap from no heat to the most heat.
+const HEAT_COLORS: [[u8; 3]; 5] = [
+    BACKGROUND,
+    [40, 40, 160],
+    [200, 40, 120],
+    [250, 150, 30],
+    [255, 255, 220],
+];
+
+/// PNG image of where the cursor spent its time, one pixel per osu!pixel.
+///
+/// The playfield is outlined and the heat is scaled logarithmically so that
+/// rarely visited spots remain visible.
+pub fn cursor_heatmap(frames: &[Frame]) -> Result<Vec<u8>, EncodingError> {
+    let width = PLAYFIELD_WIDTH + 2 * MARGIN;
+    let height = PLAYFIELD_HEIGHT + 2 * MARGIN;
+    let mut heat = vec![0.0_f32; width * height];
+
+    for pair in frames.windows(2) {
+        let weight = ((pair[1].time - pair[0].time) as f32).min(MAX_FRAME_WEIGHT);
+
+        if weight <= 0.0 {
+            continue;
+        }
+
+        let x = pair[0].x.round() as i64 + MARGIN as i64;
+        let y = pair[0].y.round() as i64 + MARGIN as i64;
+
+        if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+            heat[y as usize * width + x as usize] += weight;
+        }
+    }
+
+    // Two box blurs come close to a gaussian one
+    for _ in 0..2 {
+        blur(&mut heat, width, height);
+    }
+
+    let max = heat.iter().copied().fold(0.0, f32::max);
+    let mut pixels = Vec::with_capacity(width * height * 3);
+
+    for value in heat {
+        let intensity = if max > 0.0 {
+            (1.0 + value).ln() / (1.0 + max).ln()
+        } else {
+            0.0
+        };
+
+        pixels.extend_from_slice(&heat_color(intensity));
+    }
+
+    let border_x = [MARGIN, MARGIN + PLAYFIELD_WIDTH];
+    let border_y = [MARGIN, MARGIN + PLAYFIELD_HEIGHT];
+
+    for x in border_x[0]..=border_x[1] {
+        for y in border_y {
+            set_pixel(&mut pixels, width, x, y, BORDER);
+        }
+    }
+
+    for y in border_y[0]..=border_y[1] {
+        for x in border_x {
+            set_pixel(&mut pixels, width, x, y, BORDER);
+        }
+    }
+
+    encode(width, height, &pixels)
+}
+
+/// PNG histogram of the hit errors of all hits that aren't misses.
+///
+/// The background shows the hit windows of 300s, 100s and 50s, the grey line marks a
+/// perfect hit and the red line the mean error.
+pub fn hit_error_histogram(analysis: &Analysis) -> Result<Vec<u8>, EncodingError> {
+    let windows = &analysis.windows;
+    let range = windows.meh.max(1.0);
+    let bin_width = HISTOGRAM_WIDTH / HISTOGRAM_BINS;
+    let mut bins = [0_usize; HISTOGRAM_BINS];
+
+    for error in analysis.hits.iter().filter_map(|hit| hit.error) {
+        let bin = ((error + range) / (2.0 * range) * HISTOGRAM_BINS as f64).floor();
+        bins[(bin.max(0.0) as usize).min(HISTOGRAM_BINS - 1)] += 1;
+    }
+
+    let max = bins.iter().copied().max().unwrap_or(0).max(1);
+    let mut pixels = Vec::with_capacity(HISTOGRAM_WIDTH * HISTOGRAM_HEIGHT * 3);
+
+    for y in 0..HISTOGRAM_HEIGHT {
+        for x in 0..HISTOGRAM_WIDTH {
+            let error = (x as f64 / HISTOGRAM_WIDTH as f64 * 2.0 - 1.0) * range;
+
+            let background = match error.abs() {
+                error if error <= windows.great => [30, 40, 70],
+                error if error <= windows.ok => [30, 60, 35],
+                _ => [70, 60, 25],
+            };
+
+            let count = bins[(x / bin_width).min(HISTOGRAM_BINS - 1)];
+            // Keep some space above the largest bar
+            let bar_height = count * (HISTOGRAM_HEIGHT - 20) / max;
+            let in_gap = x % bin_width == bin_width - 1;
+
+            let color = if !in_gap && HISTOGRAM_HEIGHT - y <= bar_height {
+                [230, 230, 240]
+            } else {
+                background
+            };
+
+            pixels.extend_from_slice(&color);
+        }
+    }
+
+    let column = |error: f64| {
+        let x = (error / range + 1.0) / 2.0 * HISTOGRAM_WIDTH as f64;
+
+        (x.max(0.0) as usize).min(HISTOGRAM_WIDTH - 2)
+    };
+
+    // The mean error is scaled by the clock rate, unlike the errors of the hits
+    let mean_error = analysis
+        .hits
+        .iter()
+        .filter_map(|hit| hit.error)
+        .sum::<f64>()
+        / (analysis.early_hits + analysis.late_hits).max(1) as f64;
+
+    let center = column(0.0);
+    let mean = column(mean_error);
+
+    for y in 0..HISTOGRAM_HEIGHT {
+        set_pixel(&mut pixels, HISTOGRAM_WIDTH, center, y, [150, 150, 150]);
+        set_pixel(&mut pixels, HISTOGRAM_WIDTH, mean, y, [240, 80, 80]);
+        set_pixel(&mut pixels, HISTOGRAM_WIDTH, mean + 1, y, [240, 80, 80]);
+    }
+
+    encode(HISTOGRAM_WIDTH, HISTOGRAM_HEIGHT, &pixels)
+}
+
+/// Box blur the values horizontally and vertically.
+fn blur(values: &mut [f32], width: usize, height: usize) {
+    let mut buf = vec![0.0; values.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let start = x.saturating_sub(BLUR_RADIUS);
+            let end = (x + BLUR_RADIUS).min(width - 1);
+            let row = &values[y * width..(y + 1) * width];
+
+            buf[y * width + x] = row[start..=end].iter().sum::<f32>() / (end - start + 1) as f32;
+        }
+    }
+
+    for x in 0..width {
+        for y in 0..height {
+            let start = y.saturating_sub(BLUR_RADIUS);
+            let end = (y + BLUR_RADIUS).min(height - 1);
+            let sum: f32 = (start..=end).map(|y| buf[y * width + x]).sum();
+
+            values[y * width + x] = sum / (end - start + 1) as f32;
+        }
+    }
+}
+
+/// Interpolate between the heat colors, `intensity` being between 0 and 1.
+fn heat_color(intensity: f32) -> [u8; 3] {
+    let scaled = intensity.clamp(0.0, 1.0) * (HEAT_COLORS.len() - 1) as f32;
+    let i = (scaled.floor() as usize).min(HEAT_COLORS.len() - 2);
+    let t = scaled - i as f32;
+
+    let (from, to) = (HEAT_COLORS[i], HEAT_COLORS[i + 1]);
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+
+    [
+        mix(from[0], to[0]),
+        mix(from[1], to[1]),
+        mix(from[2], to[2]),
+    ]
+}
+
+fn set_pixel(pixels: &mut [u8], width: usize, x: usize, y: usize, color: [u8; 3]) {
+    let i = (y * width + x) * 3;
+
+    if let Some(pixel) = pixels.get_mut(i..i + 3) {
+        pixel.copy_from_slice(&color);
+    }
+}
+
+/// Encode RGB pixels as PNG.
+fn encode(width: usize, height: usize, pixels: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    let mut bytes = Vec::new();
+
+    let mut encoder = Encoder::new(&mut bytes, width as u32, height as u32);
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+
+    Ok(bytes)
+}
@@ -1,23 +1,75 @@
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     fmt::{Debug, Display, Formatter, Result as FmtResult},
+    io::ErrorKind,
+    time::Duration,
 };
 
-use tokio::sync::{
-    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
-    Mutex,
+use anyhow::{Context, Error, Result};
+use osu_db::Replay;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use tokio::{
+    fs,
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        watch, Mutex,
+    },
 };
 
-use crate::process_replays::Data;
+use crate::{
+    process_replays::{Data, TimePoints},
+    score_replay::ScoreInfo,
+    server_settings::VideoDelivery,
+    util::write_atomically,
+};
+
+const QUEUE_PATH: &str = "src/replay_queue.json";
+
+/// Seconds of rendering per second of replay that are assumed until a render finished.
+const DEFAULT_RENDER_RATE: f64 = 1.5;
+/// How much the latest render affects the estimated render rate.
+const RENDER_RATE_WEIGHT: f64 = 0.3;
 
 pub struct ReplayQueue {
+    /// Replays that are waiting for a worker
     pub queue: Mutex<VecDeque<Data>>,
-    pub status: Mutex<ReplayStatus>,
+    /// Replays that are currently being worked on, keyed by worker id
+    pub active: Mutex<BTreeMap<usize, ActiveReplay>>,
     tx: UnboundedSender<()>,
     rx: Mutex<UnboundedReceiver<()>>,
+    /// Seconds of rendering per second of replay, averaged over recent renders
+    render_rate: Mutex<f64>,
+}
+
+pub struct ActiveReplay {
+    pub data: Data,
+    pub status: ReplayStatus,
+    cancel: watch::Sender<bool>,
+}
+
+/// Lets a worker know whether its replay has been cancelled.
+pub struct Cancellation {
+    rx: watch::Receiver<bool>,
+}
+
+impl Cancellation {
+    pub fn is_cancelled(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Resolves once the replay has been cancelled.
+    pub async fn cancelled(&mut self) {
+        while !self.is_cancelled() {
+            if self.rx.changed().await.is_err() {
+                // The replay is no longer active so it can't be cancelled anymore
+                return std::future::pending().await;
+            }
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 pub enum ReplayStatus {
     Waiting,
     Downloading,
@@ -25,34 +77,283 @@ pub enum ReplayStatus {
     Uploading,
 }
 
+/// A replay that was in the queue when the bot shut down
+/// and whose user should be told about it.
+pub struct RestoreNotice {
+    pub channel: ChannelId,
+    pub user: UserId,
+    pub content: &'static str,
+}
+
+#[derive(Deserialize, Serialize)]
+struct PersistedQueue {
+    replays: Vec<PersistedReplay>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct PersistedReplay {
+    #[serde(default)]
+    guild: Option<GuildId>,
+    input_channel: ChannelId,
+    output_channel: ChannelId,
+    path: String,
+    time_points: Option<TimePoints>,
+    user: UserId,
+    #[serde(default)]
+    delivery: VideoDelivery,
+    #[serde(default)]
+    score: Option<ScoreInfo>,
+    #[serde(default)]
+    knockout: Vec<String>,
+    #[serde(default)]
+    title_template: Option<String>,
+    #[serde(default)]
+    status_message: Option<MessageId>,
+    status: ReplayStatus,
+}
+
+impl PersistedReplay {
+    fn new(data: &Data, status: ReplayStatus) -> Self {
+        Self {
+            guild: data.guild,
+            input_channel: data.input_channel,
+            output_channel: data.output_channel,
+            path: data.path.clone(),
+            time_points: data.time_points,
+            user: data.user,
+            delivery: data.delivery,
+            score: data.score.clone(),
+            knockout: data.knockout.clone(),
+            title_template: data.title_template.clone(),
+            status_message: data.status_message,
+            status,
+        }
+    }
+}
+
 impl ReplayQueue {
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Load the queue that was persisted before the last shutdown.
+    ///
+    /// Replays that were being worked on at that point are put back at the front of the queue,
+    /// replays whose file can no longer be parsed are dropped. Both cases produce a notice.
+    pub async fn restore() -> Result<(Self, Vec<RestoreNotice>)> {
+        let queue = Self::new();
+        let mut notices = Vec::new();
+
+        let content = match fs::read_to_string(QUEUE_PATH).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok((queue, notices)),
+            Err(err) => {
+                return Err(Error::new(err).context(format!("failed to read `{QUEUE_PATH}`")))
+            }
+        };
+
+        let persisted: PersistedQueue = serde_json::from_str(&content)
+            .with_context(|| format!("failed to deserialize `{QUEUE_PATH}`"))?;
+
+        for replay in persisted.replays {
+            let PersistedReplay {
+                guild,
+                input_channel,
+                output_channel,
+                path,
+                time_points,
+                user,
+                delivery,
+                score,
+                knockout,
+                title_template,
+                status_message,
+                status,
+            } = replay;
+
+            let replay = match Replay::from_file(&path) {
+                Ok(replay) => replay,
+                Err(err) => {
+                    warn!("failed to restore queued replay `{path}`: {err}");
+
+                    notices.push(RestoreNotice {
+                        channel: input_channel,
+                        user,
+                        content: "the bot restarted and your queued replay could not be restored, \
+                            please send it again",
+                    });
+
+                    continue;
+                }
+            };
+
+            if !matches!(status, ReplayStatus::Waiting) {
+                notices.push(RestoreNotice {
+                    channel: input_channel,
+                    user,
+                    content: "the bot restarted while your replay was being processed, \
+                        it has been put back at the front of the queue",
+                });
+            }
+
+            let data = Data {
+                guild,
+                input_channel,
+                output_channel,
+                path,
+                replay,
+                time_points,
+                user,
+                delivery,
+                score,
+                knockout,
+                title_template,
+                status_message,
+            };
+
+            queue.queue.lock().await.push_back(data);
+            let _ = queue.tx.send(());
+        }
+
+        queue.persist().await;
+
+        Ok((queue, notices))
+    }
+
     pub async fn push(&self, data: Data) {
         self.queue.lock().await.push_back(data);
         let _ = self.tx.send(());
+        self.persist().await;
     }
 
-    pub async fn pop(&self) -> Data {
-        self.queue.lock().await.pop_front().unwrap()
+    /// Wait for the next replay and assign it to the given worker.
+    pub async fn take(&self, worker: usize) -> (Data, Cancellation) {
+        let mut guard = self.rx.lock().await;
+
+        let data = loop {
+            let _ = guard.recv().await;
+
+            if let Some(data) = self.queue.lock().await.pop_front() {
+                break data;
+            }
+        };
+
+        drop(guard);
+
+        let (cancel, rx) = watch::channel(false);
+
+        let active = ActiveReplay {
+            data: data.clone(),
+            status: ReplayStatus::Waiting,
+            cancel,
+        };
+
+        self.active.lock().await.insert(worker, active);
+        self.persist().await;
+
+        (data, Cancellation { rx })
     }
 
-    pub async fn peek(&self) -> Data {
-        let mut guard = self.rx.lock().await;
-        let _ = guard.recv().await;
+    pub async fn set_status(&self, worker: usize, status: ReplayStatus) {
+        if let Some(active) = self.active.lock().await.get_mut(&worker) {
+            active.status = status;
+        }
 
-        return self.queue.lock().await.front().unwrap().to_owned();
+        self.persist().await;
     }
 
-    pub async fn set_status(&self, status: ReplayStatus) {
-        *self.status.lock().await = status;
+    /// Release the replay that the given worker was working on.
+    pub async fn finish(&self, worker: usize) {
+        self.active.lock().await.remove(&worker);
+        self.persist().await;
     }
 
-    pub async fn reset_peek(&self) {
-        *self.status.lock().await = ReplayStatus::Waiting;
-        self.pop().await;
+    /// Remove all waiting replays that match the predicate.
+    /// The predicate receives the 1-based position of a replay in the queue.
+    pub async fn remove_waiting(&self, mut f: impl FnMut(usize, &Data) -> bool) -> Vec<Data> {
+        let mut queue = self.queue.lock().await;
+        let mut removed = Vec::new();
+        let mut kept = VecDeque::with_capacity(queue.len());
+
+        for (data, idx) in queue.drain(..).zip(1..) {
+            if f(idx, &data) {
+                removed.push(data);
+            } else {
+                kept.push_back(data);
+            }
+        }
+
+        *queue = kept;
+        drop(queue);
+
+        if !removed.is_empty() {
+            self.persist().await;
+        }
+
+        removed
+    }
+
+    /// Signal the workers of all active replays that match the predicate to abort them.
+    pub async fn cancel_active(
+        &self,
+        mut f: impl FnMut(usize, &Data) -> bool,
+    ) -> Vec<(usize, Data)> {
+        let active = self.active.lock().await;
+
+        active
+            .iter()
+            .filter(|(&worker, active)| f(worker, &active.data))
+            .filter(|(_, active)| !active.cancel.send_replace(true))
+            .map(|(&worker, active)| (worker, active.data.clone()))
+            .collect()
+    }
+
+    /// Take a finished render of a replay that is `length` seconds long into account
+    /// for future estimates.
+    pub async fn record_render(&self, length: u32, duration: Duration) {
+        if length == 0 {
+            return;
+        }
+
+        let rate = duration.as_secs_f64() / length as f64;
+        let mut render_rate = self.render_rate.lock().await;
+        *render_rate += (rate - *render_rate) * RENDER_RATE_WEIGHT;
+    }
+
+    /// How long rendering a replay that is `length` seconds long will probably take.
+    pub async fn estimate_render(&self, length: u32) -> Duration {
+        let render_rate = *self.render_rate.lock().await;
+
+        Duration::from_secs_f64(length as f64 * render_rate)
+    }
+
+    /// Write the current queue to disk so it survives restarts.
+    async fn persist(&self) {
+        let queue = self.queue.lock().await;
+        let active = self.active.lock().await;
+
+        // Replays that are being worked on have been at the front of the queue
+        // so they go first in order to keep their position after a restart
+        let replays = active
+            .values()
+            .map(|active| PersistedReplay::new(&active.data, active.status))
+            .chain(
+                queue
+                    .iter()
+                    .map(|data| PersistedReplay::new(data, ReplayStatus::Waiting)),
+            )
+            .collect();
+
+        let persisted = PersistedQueue { replays };
+
+        let res = match serde_json::to_string(&persisted) {
+            Ok(content) => write_atomically(QUEUE_PATH, content).await,
+            Err(why) => Err(Error::new(why).context("failed to serialize replay queue")),
+        };
+
+        if let Err(err) = res {
+            warn!("{:?}", err.context("failed to persist replay queue"));
+        }
     }
 }
 
@@ -63,9 +364,10 @@ impl Default for ReplayQueue {
 
         Self {
             queue: Mutex::new(VecDeque::new()),
+            active: Mutex::new(BTreeMap::new()),
             tx,
             rx: Mutex::new(rx),
-            status: Mutex::new(ReplayStatus::Waiting),
+            render_rate: Mutex::new(DEFAULT_RENDER_RATE),
         }
     }
 }
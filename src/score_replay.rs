@@ -0,0 +1,320 @@
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    sync::Arc,
+};
+
+use anyhow::Error;
+use osu_db::{ModSet, Mode, Replay};
+use rosu_v2::prelude::{GameMode, Grade, Osu, OsuError, Score, User, UserId};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    framework::standard::CommandResult,
+    model::prelude::{Message, ReactionType},
+    prelude::Context,
+    Result as SerenityResult,
+};
+use tokio::fs;
+
+use crate::{
+    process_replays::{command_destination, queue_replay, validate_replay, Data},
+    title_template::title_template,
+    util::ReplayApi,
+    OsuClient, OsuLinkHandler, ReplayApiHandler,
+};
+
+/// The replay version written into `.osr` files that are built from online scores.
+const REPLAY_VERSION: u32 = 20210520;
+
+/// Details of an online score that are shown when its video is announced.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScoreInfo {
+    pub score_id: u64,
+    pub player: String,
+    pub grade: Grade,
+    pub pp: Option<f32>,
+    pub global_rank: Option<u32>,
+    /// Unix timestamp in seconds
+    pub date: i64,
+}
+
+impl Display for ScoreInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Score by **{}** • {}", self.player, self.grade)?;
+
+        if let Some(pp) = self.pp {
+            write!(f, " • {pp:.2}pp")?;
+        }
+
+        if let Some(rank) = self.global_rank {
+            write!(f, " • global #{rank}")?;
+        }
+
+        write!(f, " • set <t:{}:D>", self.date)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScoreReplayError {
+    #[error("it has no replay available")]
+    NoReplay,
+    #[error("failed to request score")]
+    Osu(#[from] OsuError),
+    #[error(transparent)]
+    Other(#[from] Error),
+}
+
+/// The replay of an online score.
+pub struct ScoreReplay {
+    pub replay: Replay,
+    /// The `.osr` file of the replay
+    pub bytes: Vec<u8>,
+    pub info: ScoreInfo,
+}
+
+impl ScoreReplay {
+    pub fn file_name(&self) -> String {
+        format!("{}_{}.osr", self.info.player, self.info.score_id)
+    }
+}
+
+/// Parse a score id that's either given directly or as url like
+/// `https://osu.ppy.sh/scores/osu/4088392361`.
+pub fn parse_score_id(s: &str) -> Result<u64, &'static str> {
+    let s = s.trim_start_matches('<').trim_end_matches('>');
+
+    let id = match s.split_once("osu.ppy.sh/scores/") {
+        Some((_, path)) => match path.trim_end_matches('/').split_once('/') {
+            Some(("osu", id)) => id,
+            Some(_) => return Err("Only osu!standard scores can be rendered!"),
+            None => path.trim_end_matches('/'),
+        },
+        None => s,
+    };
+
+    id.parse()
+        .map_err(|_| "You must enter a score id or a score url!")
+}
+
+/// Download the replay of the score and turn it into a `.osr` file.
+pub async fn download_replay(
+    osu: &Osu,
+    api: &ReplayApi,
+    score: Score,
+) -> Result<ScoreReplay, ScoreReplayError> {
+    if !score.replay {
+        return Err(ScoreReplayError::NoReplay);
+    }
+
+    // Scores of users don't contain the map's checksum
+    let beatmap_hash = match score.map {
+        Some(ref map) if map.checksum.is_some() => map.checksum.clone(),
+        Some(ref map) => osu.beatmap().map_id(map.map_id).await?.checksum,
+        None => None,
+    };
+
+    let replay_data = api
+        .replay_data(score.score_id)
+        .await?
+        .ok_or(ScoreReplayError::NoReplay)?;
+
+    let player = match score.user {
+        Some(ref user) => user.username.to_string(),
+        None => score.user_id.to_string(),
+    };
+
+    let stats = &score.statistics;
+
+    let replay = Replay {
+        mode: Mode::Standard,
+        version: REPLAY_VERSION,
+        beatmap_hash,
+        player_name: Some(player.clone()),
+        replay_hash: None,
+        count_300: stats.count_300 as u16,
+        count_100: stats.count_100 as u16,
+        count_50: stats.count_50 as u16,
+        count_geki: stats.count_geki as u16,
+        count_katsu: stats.count_katu as u16,
+        count_miss: stats.count_miss as u16,
+        score: score.score,
+        max_combo: score.max_combo as u16,
+        perfect_combo: score.perfect,
+        mods: ModSet::from_bits(score.mods.bits()),
+        life_graph: None,
+        timestamp: score.created_at,
+        replay_data: None,
+        raw_replay_data: Some(replay_data),
+        online_score_id: score.score_id,
+    };
+
+    let mut bytes = Vec::new();
+
+    replay
+        .to_writer(&mut bytes, None)
+        .map_err(|why| Error::new(why).context("failed to write replay"))?;
+
+    let info = ScoreInfo {
+        score_id: score.score_id,
+        player,
+        grade: score.grade,
+        pp: score.pp,
+        global_rank: score.rank_global,
+        date: score.created_at.timestamp(),
+    };
+
+    Ok(ScoreReplay {
+        replay,
+        bytes,
+        info,
+    })
+}
+
+/// Download the replay of the score and queue it, letting the user know if that's not possible.
+///
+/// `description` names the score in replies, e.g. "the most recent score of **peppy**".
+pub async fn queue_score_replay(
+    ctx: &Context,
+    msg: &Message,
+    score: Score,
+    description: &str,
+) -> CommandResult {
+    let (guild, output_channel, delivery) = match command_destination(ctx, msg).await {
+        Some(destination) => destination,
+        None => {
+            let content = "This server has no output channel yet, use the `setup` command first!";
+            msg.reply(ctx, content).await?;
+
+            return Ok(());
+        }
+    };
+
+    let (osu, api) = {
+        let data = ctx.data.read().await;
+        let osu = Arc::clone(data.get::<OsuClient>().unwrap());
+
+        (osu, data.get::<ReplayApiHandler>().cloned())
+    };
+
+    let api = match api {
+        Some(api) => api,
+        None => {
+            msg.reply(ctx, "Rendering scores is not enabled for this bot")
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let score_id = score.score_id;
+
+    let score_replay = match download_replay(&osu, &api, score).await {
+        Ok(score_replay) => score_replay,
+        Err(ScoreReplayError::NoReplay) => {
+            let content = format!("Can't render {description}, {}", ScoreReplayError::NoReplay);
+            msg.reply(ctx, content).await?;
+
+            return Ok(());
+        }
+        Err(why) => {
+            let err = Error::new(why).context(format!("failed to get replay of score {score_id}"));
+            warn!("{err:?}");
+
+            msg.reply(ctx, "something went wrong, blame mezo").await?;
+
+            return Ok(());
+        }
+    };
+
+    if let Err(rejection) = validate_replay(ctx, &score_replay.replay, None).await {
+        let content = format!("Can't render {description}, {rejection}");
+        msg.reply(ctx, content).await?;
+
+        return Ok(());
+    }
+
+    let path = format!("../Downloads/{}", score_replay.file_name());
+
+    if let Err(why) = fs::write(&path, &score_replay.bytes).await {
+        let err = Error::new(why).context(format!("failed writing to `{path}`"));
+        warn!("{err:?}");
+
+        msg.reply(ctx, "something went wrong, blame mezo").await?;
+
+        return Ok(());
+    }
+
+    let replay_data = Data {
+        guild,
+        input_channel: msg.channel_id,
+        output_channel,
+        path,
+        replay: score_replay.replay,
+        time_points: None,
+        user: msg.author.id,
+        delivery,
+        score: Some(score_replay.info),
+        knockout: Vec::new(),
+        title_template: title_template(ctx, guild, msg.author.id).await,
+        status_message: None,
+    };
+
+    queue_replay(ctx, replay_data).await;
+
+    let reaction = ReactionType::Unicode("✅".to_string());
+
+    if let Err(why) = msg.react(ctx, reaction).await {
+        let err = Error::new(why).context("failed to react after queueing score replay");
+        warn!("{err:?}");
+    }
+
+    Ok(())
+}
+
+/// The osu! user with the given name or, if there is no name, the user linked to the author.
+///
+/// `None` if the user couldn't be found, in which case the author has been told why.
+pub async fn find_player(ctx: &Context, msg: &Message, name: &str) -> SerenityResult<Option<User>> {
+    let (osu, linked) = {
+        let data = ctx.data.read().await;
+        let osu = Arc::clone(data.get::<OsuClient>().unwrap());
+
+        (
+            osu,
+            data.get::<OsuLinkHandler>().unwrap().get(msg.author.id),
+        )
+    };
+
+    let user_id = match (name, linked) {
+        ("", Some(linked)) => UserId::from(linked),
+        ("", None) => {
+            let content = "You must enter a username or link your account with the `link` command!";
+            msg.reply(ctx, content).await?;
+
+            return Ok(None);
+        }
+        (name, _) => UserId::from(name),
+    };
+
+    match osu.user(user_id).mode(GameMode::STD).await {
+        Ok(user) => Ok(Some(user)),
+        Err(OsuError::NotFound) => {
+            let content = if name.is_empty() {
+                "Couldn't find your linked osu! account anymore".to_owned()
+            } else {
+                format!("Couldn't find the osu! user `{name}`")
+            };
+
+            msg.reply(ctx, content).await?;
+
+            Ok(None)
+        }
+        Err(why) => {
+            let err = Error::new(why).context("failed to request osu! user");
+            warn!("{err:?}");
+
+            msg.reply(ctx, "something went wrong, blame mezo").await?;
+
+            Ok(None)
+        }
+    }
+}
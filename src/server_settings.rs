@@ -24,6 +24,20 @@ pub struct Server {
     pub input_channel: ChannelId,
     pub output_channel: ChannelId,
     pub prefixes: Vec<String>,
+    pub delivery: VideoDelivery,
+    /// Overrides the default video title unless the user has their own template
+    pub title_template: Option<String>,
+}
+
+/// How finished videos are sent to the output channel.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoDelivery {
+    /// Attach the video file if it fits within the server's upload limit
+    #[default]
+    Attachment,
+    /// Upload the video and post a link to it
+    Link,
 }
 
 #[derive(Deserialize)]
@@ -33,6 +47,10 @@ struct RawServer {
     output_channel: ChannelId,
     #[serde(default)]
     prefixes: Vec<String>,
+    #[serde(default)]
+    delivery: VideoDelivery,
+    #[serde(default)]
+    title_template: Option<String>,
 }
 
 struct ServersVisitor;
@@ -53,12 +71,16 @@ impl<'de> Visitor<'de> for ServersVisitor {
                 input_channel,
                 output_channel,
                 prefixes,
+                delivery,
+                title_template,
             } = raw;
 
             let server = Server {
                 input_channel,
                 output_channel,
                 prefixes,
+                delivery,
+                title_template,
             };
 
             servers.insert(server_id, server);
@@ -79,8 +101,12 @@ struct BorrowedRawServer<'s> {
 
 impl Serialize for BorrowedRawServer<'_> {
     fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
-        let mut raw =
-            s.serialize_struct("RawServer", 4 - self.server.prefixes.is_empty() as usize)?;
+        let len = 3
+            + !self.server.prefixes.is_empty() as usize
+            + (self.server.delivery != VideoDelivery::default()) as usize
+            + self.server.title_template.is_some() as usize;
+
+        let mut raw = s.serialize_struct("RawServer", len)?;
 
         raw.serialize_field("server_id", &self.server_id)?;
         raw.serialize_field("input_channel", &self.server.input_channel)?;
@@ -90,6 +116,14 @@ impl Serialize for BorrowedRawServer<'_> {
             raw.serialize_field("prefixes", &self.server.prefixes)?;
         }
 
+        if self.server.delivery != VideoDelivery::default() {
+            raw.serialize_field("delivery", &self.server.delivery)?;
+        }
+
+        if let Some(ref title_template) = self.server.title_template {
+            raw.serialize_field("title_template", title_template)?;
+        }
+
         raw.end()
     }
 }
@@ -0,0 +1,149 @@
+use std::{
+    fmt::Display,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use serenity::{
+    http::Http,
+    model::id::{ChannelId, MessageId, UserId},
+};
+
+use crate::{
+    process_replays::{send_error_message, Data},
+    replay_queue::ReplayStatus,
+};
+
+/// Discord rate limits message edits so progress updates are only sent this often.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A message in the input channel that shows how far along a replay is.
+pub struct StatusMessage {
+    http: Arc<Http>,
+    channel: ChannelId,
+    user: UserId,
+    name: String,
+    message: Option<MessageId>,
+    last_edit: Instant,
+    status: ReplayStatus,
+    progress: Option<u8>,
+}
+
+impl StatusMessage {
+    /// Post the status message of a replay that was just queued.
+    ///
+    /// `None` if the message couldn't be sent.
+    pub async fn post(http: &Http, data: &Data) -> Option<MessageId> {
+        let content = content(&data.name(), data.user, ReplayStatus::Waiting, None);
+
+        let msg_fut = data.input_channel.send_message(http, |m| {
+            m.content(content).allowed_mentions(|f| f.empty_parse())
+        });
+
+        match msg_fut.await {
+            Ok(message) => Some(message.id),
+            Err(why) => {
+                let err = Error::new(why).context("failed to send status message");
+                warn!("{err:?}");
+
+                None
+            }
+        }
+    }
+
+    /// Take over the status message that was posted when the replay was queued
+    /// or post a new one if there is none.
+    pub async fn new(http: Arc<Http>, data: &Data) -> Self {
+        let message = match data.status_message {
+            Some(message) => Some(message),
+            None => Self::post(&http, data).await,
+        };
+
+        Self {
+            http,
+            channel: data.input_channel,
+            user: data.user,
+            name: data.name(),
+            message,
+            last_edit: Instant::now(),
+            status: ReplayStatus::Waiting,
+            progress: None,
+        }
+    }
+
+    pub async fn set_status(&mut self, status: ReplayStatus) {
+        self.status = status;
+        self.progress = None;
+        let content = self.content();
+        self.edit(content).await;
+    }
+
+    /// Update the progress of the current status, skipping the edit if the last one was too recent.
+    pub async fn set_progress(&mut self, progress: u8) {
+        if self.progress == Some(progress) || self.last_edit.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+
+        self.progress = Some(progress);
+        let content = self.content();
+        self.edit(content).await;
+    }
+
+    /// Mark the replay as failed and let the user know why.
+    pub async fn fail(&mut self, reason: impl Display) {
+        let content = format!("**{}** queued by <@{}> - Failed", self.name, self.user);
+        self.edit(content).await;
+
+        send_error_message(&self.http, self.channel, self.user, reason).await;
+    }
+
+    pub async fn cancelled(&mut self) {
+        let content = format!("**{}** queued by <@{}> - Cancelled", self.name, self.user);
+        self.edit(content).await;
+    }
+
+    pub async fn done(&mut self) {
+        let content = format!("**{}** queued by <@{}> - Done", self.name, self.user);
+        self.edit(content).await;
+    }
+
+    fn content(&self) -> String {
+        content(&self.name, self.user, self.status, self.progress)
+    }
+
+    async fn edit(&mut self, content: String) {
+        let message = match self.message {
+            Some(message) => message,
+            None => return,
+        };
+
+        self.last_edit = Instant::now();
+
+        let edit_fut = self
+            .channel
+            .edit_message(&self.http, message, |m| m.content(content));
+
+        if let Err(why) = edit_fut.await {
+            let err = Error::new(why).context("failed to edit status message");
+            warn!("{err:?}");
+        }
+    }
+}
+
+fn content(name: &str, user: UserId, status: ReplayStatus, progress: Option<u8>) -> String {
+    match progress {
+        Some(progress) => format!("**{name}** queued by <@{user}> - {status} ({progress}%)"),
+        None => format!("**{name}** queued by <@{user}> - {status}"),
+    }
+}
+
+/// Extract the percentage of a danser progress line like
+/// `Progress: 42%, FPS: 245.78, ETA: 1m23s`.
+pub fn parse_progress(line: &str) -> Option<u8> {
+    let (_, rest) = line.split_once("Progress: ")?;
+    let (progress, _) = rest.split_once('%')?;
+    let progress: f32 = progress.trim().parse().ok()?;
+
+    Some(progress.clamp(0.0, 100.0) as u8)
+}
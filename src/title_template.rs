@@ -0,0 +1,394 @@
+use std::{collections::HashMap, fmt::Write, io::ErrorKind, str::FromStr};
+
+use anyhow::{Context as AnyhowContext, Error, Result};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    model::id::{GuildId, UserId},
+    prelude::Context,
+};
+use tokio::fs;
+
+use crate::{play_stats::PlayStats, util::write_atomically, ServerSettings, TitleTemplateHandler};
+
+/// Longest title in characters that all uploaders accept.
+pub const MAX_TITLE_LENGTH: usize = 100;
+
+pub const DEFAULT_TEMPLATE: &str =
+    "[{stars}⭐] {player} | {artist} - {title} [{difficulty}] {mods} {acc}%";
+
+const TEMPLATES_PATH: &str = "src/title_templates.json";
+
+/// A video title with placeholders like `{player}` that are filled in for each replay.
+#[derive(Clone, Debug)]
+pub struct TitleTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Placeholder {
+    Player,
+    Artist,
+    Title,
+    Difficulty,
+    Mods,
+    Stars,
+    Acc,
+    Pp,
+    FcPp,
+    Combo,
+    MaxCombo,
+    Misses,
+    Grade,
+    Mapper,
+}
+
+impl Placeholder {
+    const ALL: [Self; 14] = [
+        Self::Player,
+        Self::Artist,
+        Self::Title,
+        Self::Difficulty,
+        Self::Mods,
+        Self::Stars,
+        Self::Acc,
+        Self::Pp,
+        Self::FcPp,
+        Self::Combo,
+        Self::MaxCombo,
+        Self::Misses,
+        Self::Grade,
+        Self::Mapper,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Player => "player",
+            Self::Artist => "artist",
+            Self::Title => "title",
+            Self::Difficulty => "difficulty",
+            Self::Mods => "mods",
+            Self::Stars => "stars",
+            Self::Acc => "acc",
+            Self::Pp => "pp",
+            Self::FcPp => "fcpp",
+            Self::Combo => "combo",
+            Self::MaxCombo => "maxcombo",
+            Self::Misses => "misses",
+            Self::Grade => "grade",
+            Self::Mapper => "mapper",
+        }
+    }
+}
+
+/// Everything about a replay that can be put into its title.
+pub struct TitleValues {
+    pub player: String,
+    pub artist: String,
+    pub title: String,
+    pub difficulty: String,
+    /// e.g. `+HDDT`, empty for nomod
+    pub mods: String,
+    pub mapper: String,
+    pub stats: PlayStats,
+}
+
+impl TitleValues {
+    /// Values of a made up replay to preview templates with.
+    pub fn sample() -> Self {
+        Self {
+            player: "WhiteCat".to_owned(),
+            artist: "xi".to_owned(),
+            title: "FREEDOM DiVE".to_owned(),
+            difficulty: "FOUR DIMENSIONS".to_owned(),
+            mods: "+HDDT".to_owned(),
+            mapper: "Nakagawa-Kanon".to_owned(),
+            stats: PlayStats {
+                stars: 10.52,
+                pp: 1271.4,
+                fc_pp: 1271.4,
+                combo: 2385,
+                max_combo: 2385,
+                misses: 0,
+                acc: 98.81,
+                grade: "SH",
+            },
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("the template is empty")]
+    Empty,
+    #[error("a `{{` is missing its closing `}}`")]
+    Unclosed,
+    #[error("`{{{0}}}` is not a placeholder, available are {}", placeholder_list())]
+    UnknownPlaceholder(String),
+    #[error("titles can have at most {MAX_TITLE_LENGTH} characters but the preview has {0}")]
+    TooLong(usize),
+}
+
+fn placeholder_list() -> String {
+    let mut list = String::new();
+
+    for (i, placeholder) in Placeholder::ALL.iter().enumerate() {
+        if i > 0 {
+            list.push_str(", ");
+        }
+
+        let _ = write!(list, "`{{{}}}`", placeholder.name());
+    }
+
+    list
+}
+
+impl FromStr for TitleTemplate {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(TemplateError::Empty);
+        }
+
+        let mut segments = Vec::new();
+        let mut rest = s;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_owned()));
+            }
+
+            let end = rest[start..].find('}').ok_or(TemplateError::Unclosed)? + start;
+            let name = rest[start + 1..end].trim();
+
+            let placeholder = Placeholder::ALL
+                .into_iter()
+                .find(|placeholder| placeholder.name().eq_ignore_ascii_case(name))
+                .ok_or_else(|| TemplateError::UnknownPlaceholder(name.to_owned()))?;
+
+            segments.push(Segment::Placeholder(placeholder));
+            rest = &rest[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_owned()));
+        }
+
+        Ok(Self { segments })
+    }
+}
+
+impl Default for TitleTemplate {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE.parse().unwrap()
+    }
+}
+
+impl TitleTemplate {
+    /// Parse the template and make sure that it produces titles of an acceptable length.
+    ///
+    /// Returns the template and its title for the sample replay.
+    pub fn validate(s: &str) -> Result<(Self, String), TemplateError> {
+        let template: Self = s.parse()?;
+        let preview = template.fill(&TitleValues::sample());
+        let len = preview.chars().count();
+
+        if len > MAX_TITLE_LENGTH {
+            return Err(TemplateError::TooLong(len));
+        }
+
+        Ok((template, preview))
+    }
+
+    /// Fill in the placeholders, truncating the title if it gets too long.
+    pub fn render(&self, values: &TitleValues) -> String {
+        let title = self.fill(values);
+
+        if title.chars().count() <= MAX_TITLE_LENGTH {
+            return title;
+        }
+
+        let mut truncated: String = title.chars().take(MAX_TITLE_LENGTH - 1).collect();
+        truncated.push('…');
+
+        truncated
+    }
+
+    fn fill(&self, values: &TitleValues) -> String {
+        let stats = &values.stats;
+        let mut title = String::new();
+
+        for segment in self.segments.iter() {
+            let _ = match segment {
+                Segment::Text(text) => title.write_str(text),
+                Segment::Placeholder(Placeholder::Player) => title.write_str(&values.player),
+                Segment::Placeholder(Placeholder::Artist) => title.write_str(&values.artist),
+                Segment::Placeholder(Placeholder::Title) => title.write_str(&values.title),
+                Segment::Placeholder(Placeholder::Difficulty) => {
+                    title.write_str(&values.difficulty)
+                }
+                Segment::Placeholder(Placeholder::Mods) => title.write_str(&values.mods),
+                Segment::Placeholder(Placeholder::Stars) => write!(title, "{:.2}", stats.stars),
+                Segment::Placeholder(Placeholder::Acc) => write!(title, "{:.2}", stats.acc),
+                Segment::Placeholder(Placeholder::Pp) => write!(title, "{:.0}", stats.pp),
+                Segment::Placeholder(Placeholder::FcPp) => write!(title, "{:.0}", stats.fc_pp),
+                Segment::Placeholder(Placeholder::Combo) => write!(title, "{}", stats.combo),
+                Segment::Placeholder(Placeholder::MaxCombo) => {
+                    write!(title, "{}", stats.max_combo)
+                }
+                Segment::Placeholder(Placeholder::Misses) => write!(title, "{}", stats.misses),
+                Segment::Placeholder(Placeholder::Grade) => title.write_str(stats.grade),
+                Segment::Placeholder(Placeholder::Mapper) => title.write_str(&values.mapper),
+            };
+        }
+
+        // Empty placeholders like nomod's `{mods}` would leave double spaces behind
+        title.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Title templates that users chose for their own replays.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct UserTemplates {
+    templates: HashMap<UserId, String>,
+}
+
+impl UserTemplates {
+    pub async fn load() -> Result<Self> {
+        match fs::read_to_string(TEMPLATES_PATH).await {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("failed to deserialize `{TEMPLATES_PATH}`")),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(Error::new(err).context(format!("failed to read `{TEMPLATES_PATH}`"))),
+        }
+    }
+
+    pub fn get(&self, user: UserId) -> Option<&str> {
+        self.templates.get(&user).map(String::as_str)
+    }
+
+    /// Set the user's template or remove it if `template` is `None`.
+    pub fn set(&mut self, user: UserId, template: Option<String>) {
+        match template {
+            Some(template) => self.templates.insert(user, template),
+            None => self.templates.remove(&user),
+        };
+    }
+
+    /// Write the templates to disk so they survive restarts.
+    pub async fn persist(&self) {
+        let content = match serde_json::to_string(self) {
+            Ok(content) => content,
+            Err(why) => {
+                let err = Error::new(why).context("failed to serialize title templates");
+                warn!("{err:?}");
+
+                return;
+            }
+        };
+
+        if let Err(err) = write_atomically(TEMPLATES_PATH, content).await {
+            warn!("{:?}", err.context("failed to persist title templates"));
+        }
+    }
+}
+
+/// The template for a replay of the user, preferring the user's own over the guild's.
+///
+/// `None` if neither has chosen one.
+pub async fn title_template(ctx: &Context, guild: Option<GuildId>, user: UserId) -> Option<String> {
+    let data = ctx.data.read().await;
+
+    if let Some(template) = data.get::<TitleTemplateHandler>().unwrap().get(user) {
+        return Some(template.to_owned());
+    }
+
+    let guild = guild?;
+
+    data.get::<ServerSettings>()
+        .unwrap()
+        .servers
+        .get(&guild)
+        .and_then(|server| server.title_template.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_default_template() {
+        let title = TitleTemplate::default().render(&TitleValues::sample());
+
+        assert_eq!(
+            title,
+            "[10.52⭐] WhiteCat | xi - FREEDOM DiVE [FOUR DIMENSIONS] +HDDT 98.81%"
+        );
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        let template: TitleTemplate = "{ Player } {pp}pp/{FCPP}pp {combo}/{maxcombo}x {misses}❌ \
+            {grade} by {mapper}"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            template.render(&TitleValues::sample()),
+            "WhiteCat 1271pp/1271pp 2385/2385x 0❌ SH by Nakagawa-Kanon"
+        );
+    }
+
+    #[test]
+    fn collapses_empty_placeholders() {
+        let template: TitleTemplate = "{player} {mods} | {title}".parse().unwrap();
+        let mut values = TitleValues::sample();
+        values.mods.clear();
+
+        assert_eq!(template.render(&values), "WhiteCat | FREEDOM DiVE");
+    }
+
+    #[test]
+    fn truncates_long_titles() {
+        let template: TitleTemplate = "{title}".parse().unwrap();
+        let mut values = TitleValues::sample();
+        values.title = "a".repeat(MAX_TITLE_LENGTH + 20);
+
+        let title = template.render(&values);
+
+        assert_eq!(title.chars().count(), MAX_TITLE_LENGTH);
+        assert!(title.ends_with('…'));
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        let parse = |s: &str| s.parse::<TitleTemplate>();
+
+        assert!(matches!(parse("  "), Err(TemplateError::Empty)));
+        assert!(matches!(parse("{player"), Err(TemplateError::Unclosed)));
+        assert!(matches!(
+            parse("{player} {rank}"),
+            Err(TemplateError::UnknownPlaceholder(name)) if name == "rank"
+        ));
+    }
+
+    #[test]
+    fn validates_preview_length() {
+        let long = format!("{{title}} {}", "a".repeat(MAX_TITLE_LENGTH));
+
+        assert!(matches!(
+            TitleTemplate::validate(&long),
+            Err(TemplateError::TooLong(_))
+        ));
+
+        let (_, preview) = TitleTemplate::validate("{player} on {title}").unwrap();
+
+        assert_eq!(preview, "WhiteCat on FREEDOM DiVE");
+    }
+}
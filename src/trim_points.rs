@@ -0,0 +1,237 @@
+use rosu_pp::{osu::OsuGradualDifficultyAttributes, parse::HitObject, Beatmap};
+
+use crate::{
+    process_replays::TimePoints,
+    replay_analysis::{map_end, slider_end, Analysis, Judgement},
+    util::MapSections,
+};
+
+/// Milliseconds that are kept before and after an object so it's not cut off abruptly.
+const OBJECT_PADDING: f64 = 2000.0;
+
+/// A point of a map at which a replay can be trimmed.
+#[derive(Copy, Clone, Debug)]
+pub enum TrimPoint {
+    /// Milliseconds as parsed by [`TimePoints::parse_single`]
+    Time(i64),
+    /// The 1-based index of a hit object, negative to count from the last one
+    Object(i64),
+    /// The combo of a full combo, negative to count back from the map's max combo
+    Combo(i64),
+    /// The first miss of the replay
+    FirstMiss,
+    /// The 1-based index of a break, negative to count from the last one
+    Break(i64),
+    /// The 1-based index of a kiai section, negative to count from the last one
+    Kiai(i64),
+}
+
+/// Whether a point is used as start or end of the trimmed replay.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Start,
+    End,
+}
+
+impl TrimPoint {
+    /// Parse a value like `1:30`, `object:100`, `combo:-200`, `miss`, `break:2` or `kiai`.
+    pub fn parse(s: &str) -> Result<Self, &'static str> {
+        let (kind, index) = match s.split_once(':') {
+            Some((kind @ ("object" | "combo" | "break" | "kiai"), index)) => (kind, Some(index)),
+            None if matches!(s, "miss" | "break" | "kiai") => (s, None),
+            _ => return TimePoints::parse_single(s).map(Self::Time),
+        };
+
+        let index = match index.map(str::parse) {
+            Some(Ok(0)) => {
+                return Err("Indices start at 1, use negative ones to count from the end!")
+            }
+            Some(Ok(index)) => index,
+            Some(Err(_)) => return Err("A value you supplied is not a number!"),
+            None => 1,
+        };
+
+        let point = match kind {
+            "object" => Self::Object(index),
+            "combo" => Self::Combo(index),
+            "miss" => Self::FirstMiss,
+            "break" => Self::Break(index),
+            _ => Self::Kiai(index),
+        };
+
+        Ok(point)
+    }
+
+    /// Whether the map is needed to know the time of the point.
+    pub fn needs_map(self) -> bool {
+        !matches!(self, Self::Time(_))
+    }
+
+    /// Whether the replay's frames are needed to know the time of the point.
+    pub fn needs_analysis(self) -> bool {
+        matches!(self, Self::FirstMiss)
+    }
+}
+
+/// Everything that trim points are resolved with.
+pub struct MapTimeline<'m> {
+    map: &'m Beatmap,
+    sections: &'m MapSections,
+    analysis: Option<&'m Analysis>,
+}
+
+impl<'m> MapTimeline<'m> {
+    /// `analysis` is only needed for [`TrimPoint::FirstMiss`].
+    pub fn new(
+        map: &'m Beatmap,
+        sections: &'m MapSections,
+        analysis: Option<&'m Analysis>,
+    ) -> Self {
+        Self {
+            map,
+            sections,
+            analysis,
+        }
+    }
+
+    /// Milliseconds of the point, only [`TrimPoint::Time`] can be negative.
+    ///
+    /// The error is meant to be shown to the user.
+    pub fn time(&self, point: TrimPoint, edge: Edge) -> Result<i64, String> {
+        let objects = &self.map.hit_objects;
+
+        let time = match point {
+            TrimPoint::Time(time) => return Ok(time),
+            TrimPoint::Object(index) => match nth(objects, index) {
+                Some(object) => self.padded(object, edge),
+                None => return Err(format!("The map only has {} objects", objects.len())),
+            },
+            TrimPoint::Combo(combo) => {
+                let combos: Vec<_> = OsuGradualDifficultyAttributes::new(self.map, 0)
+                    .map(|attributes| attributes.max_combo as i64)
+                    .collect();
+
+                let max_combo = combos.last().copied().unwrap_or(0);
+                let target = if combo < 0 { max_combo + combo } else { combo };
+
+                // The object after which the full combo exceeds the remaining combo
+                let object = if combo < 0 {
+                    combos.iter().position(|&combo| combo > target)
+                } else {
+                    combos.iter().position(|&combo| combo >= target)
+                };
+
+                match object.filter(|_| target >= 0).and_then(|i| objects.get(i)) {
+                    Some(object) => self.padded(object, edge),
+                    None => return Err(format!("The map's max combo is only {max_combo}")),
+                }
+            }
+            TrimPoint::FirstMiss => {
+                let miss = self.analysis.and_then(|analysis| {
+                    analysis
+                        .hits
+                        .iter()
+                        .find(|hit| hit.judgement == Judgement::Miss)
+                });
+
+                match miss {
+                    Some(hit) => match edge {
+                        Edge::Start => hit.time - OBJECT_PADDING,
+                        Edge::End => hit.time + OBJECT_PADDING,
+                    },
+                    None => return Err("Couldn't find a miss in the replay".to_owned()),
+                }
+            }
+            // Starting after one break and ending before another renders the part between them
+            TrimPoint::Break(index) => match nth(&self.sections.breaks, index) {
+                Some((start, end)) => match edge {
+                    Edge::Start => *end,
+                    Edge::End => *start,
+                },
+                None if self.sections.breaks.is_empty() => {
+                    return Err("The map has no breaks".to_owned())
+                }
+                None => {
+                    let len = self.sections.breaks.len();
+
+                    return Err(format!("The map only has {len} breaks"));
+                }
+            },
+            TrimPoint::Kiai(index) => match nth(&self.sections.kiai, index) {
+                Some((start, end)) => match edge {
+                    Edge::Start => *start,
+                    Edge::End => end.unwrap_or_else(|| self.map_end()),
+                },
+                None if self.sections.kiai.is_empty() => {
+                    return Err("The map has no kiai".to_owned())
+                }
+                None => {
+                    let len = self.sections.kiai.len();
+
+                    return Err(format!("The map only has {len} kiai sections"));
+                }
+            },
+        };
+
+        Ok(time.clamp(0.0, self.map_end()) as i64)
+    }
+
+    /// Start of the object or its end, both with some room around the object.
+    fn padded(&self, object: &HitObject, edge: Edge) -> f64 {
+        match edge {
+            Edge::Start => object.start_time - OBJECT_PADDING,
+            Edge::End => self.end_time(object) + OBJECT_PADDING,
+        }
+    }
+
+    fn end_time(&self, object: &HitObject) -> f64 {
+        slider_end(self.map, object).unwrap_or_else(|| object.end_time())
+    }
+
+    fn map_end(&self) -> f64 {
+        map_end(self.map) as f64
+    }
+}
+
+/// The item at a 1-based index, negative indices counting from the end.
+fn nth<T>(items: &[T], index: i64) -> Option<&T> {
+    let i = if index < 0 {
+        items.len() as i64 + index
+    } else {
+        index - 1
+    };
+
+    usize::try_from(i).ok().and_then(|i| items.get(i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Option<TrimPoint> {
+        TrimPoint::parse(s).ok()
+    }
+
+    #[test]
+    fn parses_points() {
+        assert!(matches!(parse("1:30"), Some(TrimPoint::Time(90_000))));
+        assert!(matches!(parse("-30"), Some(TrimPoint::Time(-30_000))));
+        assert!(matches!(parse("object:100"), Some(TrimPoint::Object(100))));
+        assert!(matches!(parse("combo:-200"), Some(TrimPoint::Combo(-200))));
+        assert!(matches!(parse("miss"), Some(TrimPoint::FirstMiss)));
+        assert!(matches!(parse("break"), Some(TrimPoint::Break(1))));
+        assert!(matches!(parse("break:-1"), Some(TrimPoint::Break(-1))));
+        assert!(matches!(parse("kiai"), Some(TrimPoint::Kiai(1))));
+        assert!(matches!(parse("kiai:2"), Some(TrimPoint::Kiai(2))));
+    }
+
+    #[test]
+    fn rejects_invalid_points() {
+        assert!(parse("object:0").is_none());
+        assert!(parse("object").is_none());
+        assert!(parse("combo:x").is_none());
+        assert!(parse("miss:2").is_none());
+        assert!(parse("drain:1").is_none());
+        assert!(parse("").is_none());
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Error, Result};
+use serenity::{
+    async_trait,
+    http::Http,
+    model::{
+        guild::PremiumTier,
+        id::{ChannelId, GuildId, UserId},
+    },
+};
+use tokio::{fs, time};
+
+use crate::{
+    announcement::Announcement,
+    util::{CustomUploadApi, StreamableApi, UploadProgress},
+};
+
+/// Discord's upload limit for DMs and guilds below boost tier 2.
+const ATTACHMENT_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// How often and how long to wait for streamable to finish processing a video.
+const STREAMABLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const STREAMABLE_POLL_ATTEMPTS: u32 = 60;
+
+/// A place that rendered videos are uploaded to.
+#[async_trait]
+pub trait UploadBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn upload(&self, video: UploadRequest<'_>) -> Result<UploadedVideo, UploadError>;
+}
+
+pub struct UploadRequest<'a> {
+    pub title: &'a str,
+    pub path: &'a Path,
+    pub user: UserId,
+    /// Determines the upload limit for attachments
+    pub guild: Option<GuildId>,
+    /// The channel in which the video will be announced
+    pub channel: ChannelId,
+    /// Sent along with the video by backends that post it themselves
+    pub announcement: &'a Announcement,
+    /// Backends that upload over HTTP report their progress here
+    pub progress: UploadProgress,
+}
+
+pub enum UploadedVideo {
+    /// The video is available under this URL and still needs to be announced
+    Link(String),
+    /// The video has already been sent to the output channel
+    Posted,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    /// The backend refused the video so trying again won't help
+    #[error("{0}")]
+    Rejected(String),
+    #[error(transparent)]
+    Other(#[from] Error),
+}
+
+/// Create the backend that is selected through the `UPLOAD_BACKEND` env variable.
+pub async fn backend_from_env(http: Arc<Http>) -> Result<Box<dyn UploadBackend>> {
+    let backend: Box<dyn UploadBackend> = match env::var("UPLOAD_BACKEND").as_deref() {
+        Ok("" | "custom") | Err(_) => {
+            let url = required_env("CUSTOM_UPLOAD_URL")?;
+            let secret_key = required_env("CUSTOM_UPLOAD_SECRET")?;
+
+            let api = CustomUploadApi::new(url, secret_key)
+                .await
+                .context("failed to create custom upload api wrapper")?;
+
+            Box::new(CustomUpload { api })
+        }
+        Ok("streamable") => {
+            let username = required_env("STREAMABLE_USERNAME")?;
+            let password = required_env("STREAMABLE_PASSWORD")?;
+
+            let api = StreamableApi::new(username, password)
+                .await
+                .context("failed to create streamable api wrapper")?;
+
+            Box::new(Streamable { api })
+        }
+        Ok("local") => {
+            let dir = PathBuf::from(required_env("LOCAL_UPLOAD_DIR")?);
+            let url_prefix = required_env("LOCAL_UPLOAD_URL")?;
+
+            fs::create_dir_all(&dir)
+                .await
+                .with_context(|| format!("failed to create `{}`", dir.display()))?;
+
+            Box::new(LocalDirectory { dir, url_prefix })
+        }
+        Ok("discord") => Box::new(DiscordAttachment::new(http)),
+        Ok(other) => bail!(
+            "unknown upload backend `{other}`, expected one of `custom`, `streamable`, `local` or `discord`"
+        ),
+    };
+
+    Ok(backend)
+}
+
+fn required_env(key: &str) -> Result<String> {
+    match env::var(key) {
+        Ok(value) if !value.is_empty() => Ok(value),
+        _ => bail!("missing env variable `{key}`"),
+    }
+}
+
+/// The uploader behind shisha.mezo.xyz.
+pub struct CustomUpload {
+    api: CustomUploadApi,
+}
+
+#[async_trait]
+impl UploadBackend for CustomUpload {
+    fn name(&self) -> &str {
+        "custom uploader"
+    }
+
+    async fn upload(&self, video: UploadRequest<'_>) -> Result<UploadedVideo, UploadError> {
+        let filepath = video.path.to_string_lossy();
+
+        let response = self
+            .api
+            .upload_video(
+                video.title.to_owned(),
+                video.user,
+                &filepath,
+                video.progress,
+            )
+            .await?;
+
+        if response.error == 1 {
+            Err(UploadError::Rejected(response.text))
+        } else {
+            Ok(UploadedVideo::Link(response.text))
+        }
+    }
+}
+
+pub struct Streamable {
+    api: StreamableApi,
+}
+
+#[async_trait]
+impl UploadBackend for Streamable {
+    fn name(&self) -> &str {
+        "streamable"
+    }
+
+    async fn upload(&self, video: UploadRequest<'_>) -> Result<UploadedVideo, UploadError> {
+        let filepath = video.path.to_string_lossy();
+
+        let response = self
+            .api
+            .upload_video(video.title.to_owned(), &filepath, video.progress)
+            .await?;
+
+        let shortcode = response.shortcode;
+        let mut status = response.status;
+
+        // The link only shows the video once streamable is done processing it
+        for _ in 0..STREAMABLE_POLL_ATTEMPTS {
+            match status {
+                2 => break,
+                3 => {
+                    let reason = "streamable failed to process the video".to_owned();
+
+                    return Err(UploadError::Rejected(reason));
+                }
+                _ => time::sleep(STREAMABLE_POLL_INTERVAL).await,
+            }
+
+            match self.api.check_status_code(&shortcode).await {
+                Ok(code) => status = code,
+                Err(why) => warn!("{:?}", why.context("failed to check streamable status")),
+            }
+        }
+
+        let url = format!("https://streamable.com/{shortcode}");
+
+        Ok(UploadedVideo::Link(url))
+    }
+}
+
+/// Copies videos into a directory that is served by a web server under `url_prefix`.
+pub struct LocalDirectory {
+    dir: PathBuf,
+    url_prefix: String,
+}
+
+#[async_trait]
+impl UploadBackend for LocalDirectory {
+    fn name(&self) -> &str {
+        "local directory"
+    }
+
+    async fn upload(&self, video: UploadRequest<'_>) -> Result<UploadedVideo, UploadError> {
+        let file_name = match video.path.file_name() {
+            Some(file_name) => file_name.to_string_lossy(),
+            None => return Err(anyhow!("`{}` is not a file", video.path.display()).into()),
+        };
+
+        // Output files are reused by the workers so they need a unique name
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+
+        let name = format!("{timestamp}_{file_name}");
+        let target = self.dir.join(&name);
+
+        fs::copy(video.path, &target).await.with_context(|| {
+            format!(
+                "failed to copy `{}` to `{}`",
+                video.path.display(),
+                target.display()
+            )
+        })?;
+
+        let url = format!("{}/{name}", self.url_prefix.trim_end_matches('/'));
+
+        Ok(UploadedVideo::Link(url))
+    }
+}
+
+/// Sends videos as attachment straight into the output channel.
+pub struct DiscordAttachment {
+    http: Arc<Http>,
+}
+
+impl DiscordAttachment {
+    pub fn new(http: Arc<Http>) -> Self {
+        Self { http }
+    }
+
+    /// The largest file in bytes that can be attached to messages in the guild.
+    pub async fn limit(&self, guild: Option<GuildId>) -> u64 {
+        let guild_id = match guild {
+            Some(guild_id) => guild_id,
+            None => return ATTACHMENT_LIMIT,
+        };
+
+        match self.http.get_guild(guild_id.0).await {
+            Ok(guild) => match guild.premium_tier {
+                PremiumTier::Tier2 => 50 * 1024 * 1024,
+                PremiumTier::Tier3 => 100 * 1024 * 1024,
+                _ => ATTACHMENT_LIMIT,
+            },
+            Err(why) => {
+                let err = Error::new(why).context(format!("failed to request guild {guild_id}"));
+                warn!("{err:?}");
+
+                ATTACHMENT_LIMIT
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl UploadBackend for DiscordAttachment {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn upload(&self, video: UploadRequest<'_>) -> Result<UploadedVideo, UploadError> {
+        let size = fs::metadata(video.path)
+            .await
+            .with_context(|| format!("failed to get metadata of `{}`", video.path.display()))?
+            .len();
+
+        let limit = self.limit(video.guild).await;
+
+        if size > limit {
+            let reason = format!(
+                "the video is {:.1} MB which exceeds discord's upload limit of {} MB",
+                size as f64 / (1024.0 * 1024.0),
+                limit / (1024 * 1024)
+            );
+
+            return Err(UploadError::Rejected(reason));
+        }
+
+        // The limit applies to all files of the message so the thumbnail may have to go
+        let without_background;
+
+        let announcement = if size + video.announcement.background_size() > limit {
+            without_background = video.announcement.without_background();
+
+            &without_background
+        } else {
+            video.announcement
+        };
+
+        video
+            .channel
+            .send_message(&self.http, |m| {
+                m.add_file(video.path);
+
+                announcement.create(m, None)
+            })
+            .await
+            .context("failed to send video as attachment")?;
+
+        Ok(UploadedVideo::Posted)
+    }
+}
@@ -1,14 +1,22 @@
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use anyhow::{Context, Result};
+use futures::TryStreamExt;
 use reqwest::{
     header::CONTENT_TYPE,
     multipart::{self, Part},
-    Client, Response,
+    Body, Client, Response,
 };
 use serde::Deserialize;
 use serenity::model::prelude::UserId;
-use tokio::{fs::File, io::AsyncReadExt};
+use tokio::fs::File;
+use tokio_util::io::ReaderStream;
 
 #[derive(Clone)]
 pub struct CustomUploadApi {
@@ -36,9 +44,10 @@ impl CustomUploadApi {
         title: String,
         author: UserId,
         filepath: &str,
+        progress: UploadProgress,
     ) -> Result<UploadResponse> {
         let resp = self
-            .api_request(title, author.to_string(), filepath)
+            .api_request(title, author.to_string(), filepath, progress)
             .await?;
         let json = resp.json::<UploadResponse>().await?;
 
@@ -50,8 +59,9 @@ impl CustomUploadApi {
         title: String,
         author: String,
         files: &str,
+        progress: UploadProgress,
     ) -> Result<Response> {
-        let file = read_file(&files)
+        let file = read_file(&files, progress)
             .await
             .with_context(|| format!("failed to load file for path `{files}`"))?;
 
@@ -79,7 +89,28 @@ impl CustomUploadApi {
     }
 }
 
-pub async fn read_file<T: AsRef<Path>>(path: T) -> Result<Part> {
+/// Counts how many bytes of a file have been sent so far.
+#[derive(Clone, Default)]
+pub struct UploadProgress {
+    sent: Arc<AtomicU64>,
+}
+
+impl UploadProgress {
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.sent.store(0, Ordering::Relaxed);
+    }
+
+    fn add(&self, bytes: u64) {
+        self.sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+/// Create a multipart field that streams the file from disk instead of loading it into memory.
+pub async fn read_file<T: AsRef<Path>>(path: T, progress: UploadProgress) -> Result<Part> {
     let path = path.as_ref();
 
     let file_name = path
@@ -89,17 +120,20 @@ pub async fn read_file<T: AsRef<Path>>(path: T) -> Result<Part> {
     let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
     let mime = mime_guess::from_ext(ext).first_or_octet_stream();
 
-    let mut file = File::open(path)
+    let file = File::open(path)
         .await
         .with_context(|| format!("failed to open file `{}`", path.display()))?;
 
-    let mut bytes = Vec::new();
-
-    file.read_to_end(&mut bytes)
+    let length = file
+        .metadata()
         .await
-        .with_context(|| format!("failed to read file `{}`", path.display()))?;
+        .with_context(|| format!("failed to get metadata of `{}`", path.display()))?
+        .len();
+
+    let stream = ReaderStream::new(file).inspect_ok(move |chunk| progress.add(chunk.len() as u64));
 
-    let field = Part::bytes(bytes).mime_str(mime.essence_str())?;
+    let field =
+        Part::stream_with_length(Body::wrap_stream(stream), length).mime_str(mime.essence_str())?;
 
     let part = if let Some(file_name) = file_name {
         field.file_name(file_name)
@@ -1,8 +1,17 @@
-mod levenshtein;
-pub use levenshtein::*;
-
 mod streamable_wrapper;
 pub use streamable_wrapper::*;
 
 mod custom_upload_wrapper;
 pub use custom_upload_wrapper::*;
+
+mod osu_file;
+pub use osu_file::*;
+
+mod replay_api;
+pub use replay_api::*;
+
+mod replay_frames;
+pub use replay_frames::*;
+
+mod persist;
+pub use persist::*;
@@ -0,0 +1,267 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+use tokio::fs;
+
+/// A `.osu` file of a downloaded mapset.
+pub struct OsuFile {
+    pub path: PathBuf,
+    pub metadata: Metadata,
+    /// The background image of the difficulty if it has one
+    pub background: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    pub artist: String,
+    pub title: String,
+    /// The difficulty name
+    pub version: String,
+    pub creator: String,
+}
+
+impl Metadata {
+    pub fn parse(content: &str) -> Self {
+        let mut metadata = Self::default();
+
+        let section = content
+            .lines()
+            .skip_while(|line| line.trim() != "[Metadata]")
+            .skip(1)
+            .take_while(|line| !line.trim_start().starts_with('['));
+
+        for line in section {
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+
+            match key {
+                "Artist" => metadata.artist = value.to_owned(),
+                "Title" => metadata.title = value.to_owned(),
+                "Version" => metadata.version = value.to_owned(),
+                "Creator" => metadata.creator = value.to_owned(),
+                _ => {}
+            }
+        }
+
+        metadata
+    }
+}
+
+/// Breaks and kiai sections of a map as start and end in milliseconds.
+#[derive(Clone, Debug, Default)]
+pub struct MapSections {
+    pub breaks: Vec<(f64, f64)>,
+    /// Kiai that lasts until the end of the map has no end
+    pub kiai: Vec<(f64, Option<f64>)>,
+}
+
+impl MapSections {
+    pub fn parse(content: &str) -> Self {
+        let mut sections = Self::default();
+
+        // Breaks are given in the `[Events]` section by lines like `2,start,end`
+        for line in section(content, "[Events]") {
+            let mut split = line.split(',').map(str::trim);
+
+            if let (Some("2" | "Break"), Some(Ok(start)), Some(Ok(end))) = (
+                split.next(),
+                split.next().map(str::parse),
+                split.next().map(str::parse),
+            ) {
+                sections.breaks.push((start, end));
+            }
+        }
+
+        // Every timing point turns kiai on or off by the first bit of its eighth value
+        for line in section(content, "[TimingPoints]") {
+            let values: Vec<_> = line.split(',').map(str::trim).collect();
+
+            let (time, effects) = match (values.first(), values.get(7)) {
+                (Some(time), Some(effects)) => match (time.parse(), effects.parse::<u32>()) {
+                    (Ok(time), Ok(effects)) => (time, effects),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+
+            let kiai = effects & 1 == 1;
+
+            match sections.kiai.last_mut() {
+                Some((_, end @ None)) if !kiai => *end = Some(time),
+                Some((_, None)) => {}
+                _ if kiai => sections.kiai.push((time, None)),
+                _ => {}
+            }
+        }
+
+        sections
+    }
+}
+
+impl OsuFile {
+    /// Find the `.osu` file in the given mapset directory whose MD5 hash matches `hash`.
+    pub async fn find_by_hash(mapset_dir: impl AsRef<Path>, hash: &str) -> Result<Option<Self>> {
+        let mapset_dir = mapset_dir.as_ref();
+
+        let mut entries = fs::read_dir(mapset_dir)
+            .await
+            .with_context(|| format!("failed to read dir `{}`", mapset_dir.display()))?;
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .with_context(|| format!("failed to get entry of `{}`", mapset_dir.display()))?
+        {
+            let path = entry.path();
+
+            if path.extension().and_then(|ext| ext.to_str()) != Some("osu") {
+                continue;
+            }
+
+            let bytes = fs::read(&path)
+                .await
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+
+            if !format!("{:x}", md5::compute(&bytes)).eq_ignore_ascii_case(hash) {
+                continue;
+            }
+
+            let content = String::from_utf8_lossy(&bytes);
+            let metadata = Metadata::parse(&content);
+            let background = match parse_background(&content) {
+                Some(file) => background_path(mapset_dir, file).await,
+                None => None,
+            };
+
+            return Ok(Some(Self {
+                path,
+                metadata,
+                background,
+            }));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Download the `.osu` file of the map from the osu! website.
+///
+/// `None` if the map has been updated since the replay with the given MD5 hash was set.
+pub async fn download_osu_file(
+    client: &Client,
+    map_id: u32,
+    hash: &str,
+) -> Result<Option<Vec<u8>>> {
+    let bytes = client
+        .get(format!("https://osu.ppy.sh/osu/{map_id}"))
+        .send()
+        .await
+        .with_context(|| format!("failed to request .osu file of map {map_id}"))?
+        .error_for_status()
+        .with_context(|| format!("received error status for .osu file of map {map_id}"))?
+        .bytes()
+        .await
+        .with_context(|| format!("failed to receive .osu file of map {map_id}"))?;
+
+    // The website always serves the latest version of the map
+    if !format!("{:x}", md5::compute(&bytes)).eq_ignore_ascii_case(hash) {
+        return Ok(None);
+    }
+
+    Ok(Some(bytes.to_vec()))
+}
+
+/// The path of the background image within the mapset directory.
+///
+/// `None` unless `file` is the plain name of an image that's actually inside the directory
+/// since maps could otherwise point at any file the bot can read.
+async fn background_path(mapset_dir: &Path, file: &str) -> Option<PathBuf> {
+    let mut components = Path::new(file).components();
+
+    let name = match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) => name,
+        _ => return None,
+    };
+
+    let is_image = Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_ascii_lowercase().as_str(), "jpg" | "jpeg" | "png"));
+
+    if !is_image {
+        return None;
+    }
+
+    // Symlinks must not lead outside of the directory either
+    let mapset_dir = fs::canonicalize(mapset_dir).await.ok()?;
+    let path = fs::canonicalize(mapset_dir.join(name)).await.ok()?;
+
+    path.starts_with(&mapset_dir).then_some(path)
+}
+
+/// The lines of a section like `[Events]`.
+fn section<'c>(content: &'c str, name: &'c str) -> impl Iterator<Item = &'c str> {
+    content
+        .lines()
+        .skip_while(move |line| line.trim() != name)
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('['))
+}
+
+/// The file name of the background image which is given in the `[Events]` section
+/// by a line like `0,0,"bg.jpg",0,0`.
+fn parse_background(content: &str) -> Option<&str> {
+    section(content, "[Events]").find_map(|line| {
+        let mut split = line.split(',');
+
+        match (split.next(), split.next(), split.next()) {
+            (Some("0"), Some(_), Some(file)) => Some(file.trim().trim_matches('"')),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OSU_FILE: &str = "osu file format v14
+
+[Events]
+//Background and Video events
+0,0,\"bg.jpg\",0,0
+//Break Periods
+2,20000,25000
+Break,60000,64500
+
+[TimingPoints]
+0,500,4,2,0,60,1,0
+10000,-100,4,2,0,60,0,1
+15000,-100,4,2,0,60,0,0
+30000,-100,4,2,0,60,0,1
+35000,-50,4,2,0,60,0,1
+80000,-100,4,2,0,60,0,1
+
+[HitObjects]
+256,192,1000,1,0,0:0:0:0:
+";
+
+    #[test]
+    fn parses_sections() {
+        let sections = MapSections::parse(OSU_FILE);
+
+        assert_eq!(sections.breaks, [(20000.0, 25000.0), (60000.0, 64500.0)]);
+        assert_eq!(sections.kiai, [(10000.0, Some(15000.0)), (30000.0, None)]);
+    }
+
+    #[test]
+    fn parses_map_without_sections() {
+        let sections = MapSections::parse("osu file format v14\n\n[HitObjects]\n");
+
+        assert!(sections.breaks.is_empty());
+        assert!(sections.kiai.is_empty());
+    }
+}
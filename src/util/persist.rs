@@ -0,0 +1,20 @@
+use anyhow::{Context, Result};
+use tokio::fs;
+
+/// Replace the content of the file at `path` without ever leaving it half-written.
+///
+/// Callers must make sure that only one write to the same path runs at a time
+/// since all writes go through the same temporary file.
+pub async fn write_atomically(path: &str, content: impl AsRef<[u8]>) -> Result<()> {
+    let tmp_path = format!("{path}.tmp");
+
+    fs::write(&tmp_path, content)
+        .await
+        .with_context(|| format!("failed writing to `{tmp_path}`"))?;
+
+    fs::rename(&tmp_path, path)
+        .await
+        .with_context(|| format!("failed to rename `{tmp_path}` to `{path}`"))?;
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::Deserialize;
+
+/// Replays can only be downloaded through api v2 with a user's authorization
+/// and rosu-v2 has no endpoint for them, so they are requested from api v1 instead.
+pub struct ReplayApi {
+    client: Client,
+    api_key: String,
+}
+
+#[derive(Deserialize)]
+struct ReplayResponse {
+    content: Option<String>,
+    error: Option<String>,
+}
+
+impl ReplayApi {
+    pub fn new(client: Client, api_key: String) -> Self {
+        Self { client, api_key }
+    }
+
+    /// The compressed replay data of an osu!standard score,
+    /// `None` if the score has no replay available.
+    pub async fn replay_data(&self, score_id: u64) -> Result<Option<Vec<u8>>> {
+        let score_id = score_id.to_string();
+
+        let response: ReplayResponse = self
+            .client
+            .get("https://osu.ppy.sh/api/get_replay")
+            .query(&[("k", self.api_key.as_str()), ("s", &score_id), ("m", "0")])
+            .send()
+            .await
+            .context("failed to request replay")?
+            .error_for_status()
+            .context("received error status when requesting replay")?
+            .json()
+            .await
+            .context("failed to deserialize replay response")?;
+
+        match (response.content, response.error) {
+            (Some(content), _) => base64::decode(content)
+                .map(Some)
+                .context("failed to decode replay content"),
+            (None, Some(error)) if error.contains("not available") => Ok(None),
+            (None, Some(error)) => bail!("failed to get replay: {error}"),
+            (None, None) => bail!("replay response contains neither content nor error"),
+        }
+    }
+}
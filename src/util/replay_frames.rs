@@ -0,0 +1,146 @@
+use std::io::{Error as IoError, ErrorKind, Result as IoResult, Write};
+
+use anyhow::{Context, Result};
+use lzma_rs::decompress::Options;
+use osu_db::{replay::Action, Replay};
+
+/// The delta of the last action whose `z` holds the RNG seed instead of buttons.
+const SEED_DELTA: i64 = -12345;
+
+/// Replay frames of even the longest maps decompress to far less than this many bytes.
+const MAX_REPLAY_DATA_LEN: usize = 32 * 1024 * 1024;
+
+/// A cursor position of a replay at a point in time.
+#[derive(Copy, Clone, Debug)]
+pub struct Frame {
+    /// Milliseconds since the start of the map
+    pub time: i64,
+    pub x: f32,
+    pub y: f32,
+    /// The pressed buttons as osu!standard button bits
+    pub buttons: u32,
+}
+
+/// The replay's actions, decompressing them if osu-db didn't already.
+pub fn replay_actions(replay: &Replay) -> Result<Vec<Action>> {
+    if let Some(ref actions) = replay.replay_data {
+        return Ok(actions.clone());
+    }
+
+    let raw = replay
+        .raw_replay_data
+        .as_deref()
+        .context("the replay has no replay data")?;
+
+    let data = decompress(raw).context("failed to decompress replay data")?;
+    let data = String::from_utf8_lossy(&data);
+
+    data.split(',')
+        .filter(|action| !action.is_empty())
+        .map(|action| parse_action(action).with_context(|| format!("invalid action `{action}`")))
+        .collect()
+}
+
+/// The replay's frames with absolute times, leaving out the seed.
+pub fn replay_frames(replay: &Replay) -> Result<Vec<Frame>> {
+    let mut time = 0;
+
+    let frames = replay_actions(replay)?
+        .into_iter()
+        .filter(|action| action.delta != SEED_DELTA)
+        .map(|action| {
+            time += action.delta;
+
+            Frame {
+                time,
+                x: action.x,
+                y: action.y,
+                buttons: action.z as u32,
+            }
+        })
+        .collect();
+
+    Ok(frames)
+}
+
+/// Decompress the LZMA stream of the replay's actions without trusting its header.
+fn decompress(raw: &[u8]) -> Result<Vec<u8>> {
+    let options = Options {
+        memlimit: Some(MAX_REPLAY_DATA_LEN),
+        ..Default::default()
+    };
+
+    let mut output = BoundedOutput(Vec::new());
+    lzma_rs::lzma_decompress_with_options(&mut &raw[..], &mut output, &options)?;
+
+    Ok(output.0)
+}
+
+/// Refuses to grow beyond [`MAX_REPLAY_DATA_LEN`] bytes.
+struct BoundedOutput(Vec<u8>);
+
+impl Write for BoundedOutput {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        if self.0.len() + buf.len() > MAX_REPLAY_DATA_LEN {
+            let msg = format!("replay data exceeds {MAX_REPLAY_DATA_LEN} bytes");
+
+            return Err(IoError::new(ErrorKind::InvalidData, msg));
+        }
+
+        self.0.extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+fn parse_action(action: &str) -> Option<Action> {
+    let mut split = action.split('|');
+
+    let action = Action {
+        delta: split.next()?.trim().parse().ok()?,
+        x: split.next()?.parse().ok()?,
+        y: split.next()?.parse().ok()?,
+        z: split.next()?.parse().ok()?,
+    };
+
+    Some(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        Replay::from_bytes(include_bytes!("../../tests/fixtures/replay.osr")).unwrap()
+    }
+
+    #[test]
+    fn decodes_replay_frames() {
+        let frames = replay_frames(&replay()).unwrap();
+
+        assert_eq!(frames.len(), 3002);
+        assert_eq!(frames[0].time, 0);
+        assert_eq!((frames[2].x, frames[2].y), (405.9531, 194.4997));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let mut replay = replay();
+        let raw = replay.raw_replay_data.as_mut().unwrap();
+        raw.truncate(raw.len() / 2);
+
+        assert!(replay_frames(&replay).is_err());
+    }
+
+    #[test]
+    fn limits_decompressed_size() {
+        // 40 MiB of zeros
+        let raw = include_bytes!("../../tests/fixtures/zeros.lzma");
+
+        assert!(decompress(raw).is_err());
+    }
+}
@@ -1,17 +1,14 @@
-use std::path::Path;
-
 use anyhow::{Context, Result};
 use base64::encode;
 use reqwest::{
     header::{HeaderMap, HeaderValue, AUTHORIZATION},
-    multipart::{self, Part},
-    Client, Response,
+    multipart, Client, Response,
 };
 use serde::Deserialize;
-use tokio::fs::File;
-use tokio::io::AsyncReadExt;
 
-pub struct _StreamableApi {
+use super::{read_file, UploadProgress};
+
+pub struct StreamableApi {
     pub client: Client,
 }
 
@@ -21,13 +18,13 @@ pub struct StatusResponse {
 }
 
 #[derive(Deserialize)]
-pub struct UploadResponse {
+pub struct StreamableUploadResponse {
     pub shortcode: String,
     pub status: i8,
 }
 
-impl _StreamableApi {
-    pub async fn _new(username: String, password: String) -> Result<Self> {
+impl StreamableApi {
+    pub async fn new(username: String, password: String) -> Result<Self> {
         let mut headers = HeaderMap::new();
         let value = format!("Basic {}", encode(format!("{username}:{password}")));
         headers.insert(AUTHORIZATION, HeaderValue::from_str(&value)?);
@@ -36,15 +33,20 @@ impl _StreamableApi {
         Ok(Self { client })
     }
 
-    pub async fn _upload_video(&self, title: String, filepath: &str) -> Result<UploadResponse> {
+    pub async fn upload_video(
+        &self,
+        title: String,
+        filepath: &str,
+        progress: UploadProgress,
+    ) -> Result<StreamableUploadResponse> {
         let url = "https://api.streamable.com/upload";
-        let resp = self._api_request(url, title, filepath).await?;
-        let json = resp.json::<UploadResponse>().await?;
+        let resp = self.api_request(url, title, filepath, progress).await?;
+        let json = resp.json::<StreamableUploadResponse>().await?;
 
         Ok(json)
     }
 
-    pub async fn _check_status_code(&self, shortcode: &str) -> Result<i8> {
+    pub async fn check_status_code(&self, shortcode: &str) -> Result<i8> {
         let url = format!("https://api.streamable.com/videos/{shortcode}");
         let resp = self.client.get(url).send().await?.bytes().await?;
         let custom_resp: StatusResponse = serde_json::from_slice(&resp)?;
@@ -52,8 +54,14 @@ impl _StreamableApi {
         Ok(custom_resp.status)
     }
 
-    pub async fn _api_request(&self, url: &str, data: String, files: &str) -> Result<Response> {
-        let file = _read_file(&files)
+    pub async fn api_request(
+        &self,
+        url: &str,
+        data: String,
+        files: &str,
+        progress: UploadProgress,
+    ) -> Result<Response> {
+        let file = read_file(&files, progress)
             .await
             .with_context(|| format!("failed to load file for path `{files}`"))?;
 
@@ -66,34 +74,3 @@ impl _StreamableApi {
         Ok(resp)
     }
 }
-
-pub async fn _read_file<T: AsRef<Path>>(path: T) -> Result<Part> {
-    let path = path.as_ref();
-
-    let file_name = path
-        .file_name()
-        .map(|filename| filename.to_string_lossy().into_owned());
-
-    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
-    let mime = mime_guess::from_ext(ext).first_or_octet_stream();
-
-    let mut file = File::open(path)
-        .await
-        .with_context(|| format!("failed to open file `{}`", path.display()))?;
-
-    let mut bytes = Vec::new();
-
-    file.read_to_end(&mut bytes)
-        .await
-        .with_context(|| format!("failed to read file `{}`", path.display()))?;
-
-    let field = Part::bytes(bytes).mime_str(mime.essence_str())?;
-
-    let part = if let Some(file_name) = file_name {
-        field.file_name(file_name)
-    } else {
-        field
-    };
-
-    Ok(part)
-}